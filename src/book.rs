@@ -5,13 +5,12 @@ use crate::moves::{generate_moves, move_from, move_to, move_promotion_piece};
 use crate::helpers::algebraic_to_sq;
 use std::collections::HashMap;
 use lazy_static::lazy_static;
-use serde_yaml;
 
 fn parse_mapping(state: &mut State, mapping: serde_yaml::Mapping, book: &mut HashMap<u64, Vec<BitMove>>) {
-    let copy = state.clone();
+    let copy = *state;
 
     for v in mapping.into_iter() {
-        let moves = generate_moves(&state);
+        let moves = generate_moves(state);
 
         match v.0 {
             serde_yaml::Value::String(s) => {
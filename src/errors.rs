@@ -1,7 +1,20 @@
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct InvalidFenError {
     pub fen: String
 }
 
 #[derive(Debug)]
-pub struct IllegalMoveError;
\ No newline at end of file
+pub struct IllegalMoveError;
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct InvalidOptionError {
+    pub name: String
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct InvalidNetworkError {
+    pub path: String
+}
\ No newline at end of file
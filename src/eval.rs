@@ -1,194 +1,197 @@
-use crate::bitboards::pop_ls1b;
+use crate::bitboards::{pop_ls1b, count_bits, get_ls1b};
+use crate::attacks::{KNIGHT_ATTACKS, bishop_attacks, rook_attacks, queen_attacks};
 use crate::colours::Colour;
 use crate::pieces::Piece;
 use crate::state::State;
-use crate::helpers::mirror_sq;
+use crate::castling::{CastleType, decode_castling};
+use crate::helpers::{mirror_sq, sq_file, sq_rank};
+use crate::king_safety::evaluate_king_safety;
+use crate::params::EvalParams;
+use crate::default_params::PARAMS;
+use crate::pawns::{evaluate_pawns, evaluate_pawns_with, pawn_attacks, file_mask, adjacent_files, forward_ranks, relative_rank};
 use std::cmp::min;
 
-const MG_PC_VALS: [isize; 6] = [
-    82,
-    337,
-    365,
-    477,
-    1025,
-    0
-];
-
-const EG_PC_VALS: [isize; 6] = [
-    94,
-    281,
-    297,
-    512,
-    936,
-    0
-];
-
-const MG_SQ_VALS: [[isize; 64]; 6] = [
-    [
-        0,   0,   0,   0,   0,   0,  0,   0,
-        98, 134,  61,  95,  68, 126, 34, -11,
-        -6,   7,  26,  31,  65,  56, 25, -20,
-       -14,  13,   6,  21,  23,  12, 17, -23,
-       -27,  -2,  -5,  12,  17,   6, 10, -25,
-       -26,  -4,  -4, -10,   3,   3, 33, -12,
-       -35,  -1, -20, -23, -15,  24, 38, -22,
-         0,   0,   0,   0,   0,   0,  0,   0,
-    ],
-    [
-        -167, -89, -34, -49,  61, -97, -15, -107,
-        -73, -41,  72,  36,  23,  62,   7,  -17,
-        -47,  60,  37,  65,  84, 129,  73,   44,
-         -9,  17,  19,  53,  37,  69,  18,   22,
-        -13,   4,  16,  13,  28,  19,  21,   -8,
-        -23,  -9,  12,  10,  19,  17,  25,  -16,
-        -29, -53, -12,  -3,  -1,  18, -14,  -19,
-       -105, -21, -58, -33, -17, -28, -19,  -23,
-    ],
-    [
-        -29,   4, -82, -37, -25, -42,   7,  -8,
-        -26,  16, -18, -13,  30,  59,  18, -47,
-        -16,  37,  43,  40,  35,  50,  37,  -2,
-         -4,   5,  19,  50,  37,  37,   7,  -2,
-         -6,  13,  13,  26,  34,  12,  10,   4,
-          0,  15,  15,  15,  14,  27,  18,  10,
-          4,  15,  16,   0,   7,  21,  33,   1,
-        -33,  -3, -14, -21, -13, -12, -39, -21,
-    ],
-    [
-        32,  42,  32,  51, 63,  9,  31,  43,
-        27,  32,  58,  62, 80, 67,  26,  44,
-        -5,  19,  26,  36, 17, 45,  61,  16,
-       -24, -11,   7,  26, 24, 35,  -8, -20,
-       -36, -26, -12,  -1,  9, -7,   6, -23,
-       -45, -25, -16, -17,  3,  0,  -5, -33,
-       -44, -16, -20,  -9, -1, 11,  -6, -71,
-       -19, -13,   1,  17, 16,  7, -37, -26,
-    ],
-    [
-        -28,   0,  29,  12,  59,  44,  43,  45,
-        -24, -39,  -5,   1, -16,  57,  28,  54,
-        -13, -17,   7,   8,  29,  56,  47,  57,
-        -27, -27, -16, -16,  -1,  17,  -2,   1,
-         -9, -26,  -9, -10,  -2,  -4,   3,  -3,
-        -14,   2, -11,  -2,  -5,   2,  14,   5,
-        -35,  -8,  11,   2,   8,  15,  -3,   1,
-         -1, -18,  -9,  10, -15, -25, -31, -50,
-    ],
-    [
-        -65,  23,  16, -15, -56, -34,   2,  13,
-        29,  -1, -20,  -7,  -8,  -4, -38, -29,
-        -9,  24,   2, -16, -20,   6,  22, -22,
-       -17, -20, -12, -27, -30, -25, -14, -36,
-       -49,  -1, -27, -39, -46, -44, -33, -51,
-       -14, -14, -22, -46, -44, -30, -15, -27,
-         1,   7,  -8, -64, -43, -16,   9,   8,
-       -15,  36,  12, -54,   8, -28,  24,  14,
-    ]
-];
-
-const EG_SQ_VALS: [[isize; 64]; 6] = [
-    [
-        0,   0,   0,   0,   0,   0,   0,   0,
-        178, 173, 158, 134, 147, 132, 165, 187,
-         94, 100,  85,  67,  56,  53,  82,  84,
-         32,  24,  13,   5,  -2,   4,  17,  17,
-         13,   9,  -3,  -7,  -7,  -8,   3,  -1,
-          4,   7,  -6,   1,   0,  -5,  -1,  -8,
-         13,   8,   8,  10,  13,   0,   2,  -7,
-          0,   0,   0,   0,   0,   0,   0,   0,
-    ],
-    [
-        -58, -38, -13, -28, -31, -27, -63, -99,
-        -25,  -8, -25,  -2,  -9, -25, -24, -52,
-        -24, -20,  10,   9,  -1,  -9, -19, -41,
-        -17,   3,  22,  22,  22,  11,   8, -18,
-        -18,  -6,  16,  25,  16,  17,   4, -18,
-        -23,  -3,  -1,  15,  10,  -3, -20, -22,
-        -42, -20, -10,  -5,  -2, -20, -23, -44,
-        -29, -51, -23, -15, -22, -18, -50, -64,
-    ],
-    [
-        -14, -21, -11,  -8, -7,  -9, -17, -24,
-        -8,  -4,   7, -12, -3, -13,  -4, -14,
-         2,  -8,   0,  -1, -2,   6,   0,   4,
-        -3,   9,  12,   9, 14,  10,   3,   2,
-        -6,   3,  13,  19,  7,  10,  -3,  -9,
-       -12,  -3,   8,  10, 13,   3,  -7, -15,
-       -14, -18,  -7,  -1,  4,  -9, -15, -27,
-       -23,  -9, -23,  -5, -9, -16,  -5, -17,
-    ],
-    [
-        13, 10, 18, 15, 12,  12,   8,   5,
-        11, 13, 13, 11, -3,   3,   8,   3,
-         7,  7,  7,  5,  4,  -3,  -5,  -3,
-         4,  3, 13,  1,  2,   1,  -1,   2,
-         3,  5,  8,  4, -5,  -6,  -8, -11,
-        -4,  0, -5, -1, -7, -12,  -8, -16,
-        -6, -6,  0,  2, -9,  -9, -11,  -3,
-        -9,  2,  3, -1, -5, -13,   4, -20,
-    ],
-    [
-        -9,  22,  22,  27,  27,  19,  10,  20,
-        -17,  20,  32,  41,  58,  25,  30,   0,
-        -20,   6,   9,  49,  47,  35,  19,   9,
-          3,  22,  24,  45,  57,  40,  57,  36,
-        -18,  28,  19,  47,  31,  34,  39,  23,
-        -16, -27,  15,   6,   9,  17,  10,   5,
-        -22, -23, -30, -16, -16, -23, -36, -32,
-        -33, -28, -22, -43,  -5, -32, -20, -41,
-    ],
-    [
-        -74, -35, -18, -18, -11,  15,   4, -17,
-        -12,  17,  14,  17,  17,  38,  23,  11,
-         10,  17,  23,  15,  20,  45,  44,  13,
-         -8,  22,  24,  27,  26,  33,  26,   3,
-        -18,  -4,  21,  24,  27,  23,   9, -11,
-        -19,  -3,  11,  21,  23,  16,   7,  -9,
-        -27, -11,   4,  13,  14,   4,  -5, -17,
-        -53, -34, -21, -11, -28, -14, -24, -43
-    ]
-];
-
-const PHASE_INCREMENTS: [isize; 6] = [
-    0,
-    1,
-    1,
-    2,
-    4,
-    0
-];
+// Per safe square a piece can move to, mobility is counted from a typical number of squares so that the bonus is centred on zero
+const MOBILITY_BASELINE: [isize; 6] = [0, 4, 6, 6, 12, 0];
 
-pub fn eval(state: &State) -> isize {
-    let mut mg: [isize; 2] = [0; 2];
-    let mut eg: [isize; 2] = [0; 2];
+// The midgame and endgame value of a piece on a square, from White's point of view
+pub fn piece_square_value(params: &EvalParams, colour: Colour, piece: Piece, sq: usize) -> (isize, isize) {
+    match colour {
+        Colour::White => {
+            let sq = mirror_sq(sq);
+            (params.mg_pc_vals[piece as usize] + params.mg_sq_vals[piece as usize][sq], params.eg_pc_vals[piece as usize] + params.eg_sq_vals[piece as usize][sq])
+        },
+        Colour::Black => (-params.mg_pc_vals[piece as usize] - params.mg_sq_vals[piece as usize][sq], -params.eg_pc_vals[piece as usize] - params.eg_sq_vals[piece as usize][sq])
+    }
+}
+
+// The midgame score, endgame score and phase computed from scratch, which the state otherwise keeps updated as moves are made
+pub fn material_and_psts(state: &State, params: &EvalParams) -> (isize, isize, isize) {
+    let mut mg: isize = 0;
+    let mut eg: isize = 0;
     let mut phase: isize = 0;
     for piece in &[Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
         for colour in &[Colour::White, Colour::Black] {
             let mut bb = state.pieces[*piece as usize] & state.colours[*colour as usize];
             while bb != 0 {
-                let sq = match colour {
-                    Colour::White => mirror_sq(pop_ls1b(&mut bb)),
-                    Colour::Black => pop_ls1b(&mut bb)
+                let (mg_value, eg_value) = piece_square_value(params, *colour, *piece, pop_ls1b(&mut bb));
+                mg += mg_value;
+                eg += eg_value;
+                phase += params.phase_increments[*piece as usize];
+            }
+        }
+    }
+
+    (mg, eg, phase)
+}
+
+// Mobility and the terms for where each kind of piece stands, from White's point of view
+pub fn evaluate_pieces(state: &State, params: &EvalParams) -> (isize, isize) {
+    let mut mg = 0;
+    let mut eg = 0;
+    let pawns = state.pieces[Piece::Pawn as usize];
+
+    for colour in &[Colour::White, Colour::Black] {
+        let sign = match colour {
+            Colour::White => 1,
+            Colour::Black => -1
+        };
+        let own = state.colours[*colour as usize];
+        let own_pawns = pawns & own;
+        let enemy_pawns = pawns & state.colours[!*colour as usize];
+        let enemy_king = get_ls1b(state.pieces[Piece::King as usize] & state.colours[!*colour as usize]);
+        let own_king = get_ls1b(state.pieces[Piece::King as usize] & own);
+        // Squares attacked by enemy pawns aren't safe for any piece to go to
+        let safe = !own & !pawn_attacks(enemy_pawns, !*colour);
+        let (mut colour_mg, mut colour_eg) = (0, 0);
+        let mut add = |(term_mg, term_eg): (isize, isize)| {
+            colour_mg += term_mg;
+            colour_eg += term_eg;
+        };
+
+        if count_bits(state.pieces[Piece::Bishop as usize] & own) >= 2 {
+            add(params.bishop_pair);
+        }
+
+        for piece in &[Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+            let mut bb = state.pieces[*piece as usize] & own;
+            while bb != 0 {
+                let sq = pop_ls1b(&mut bb);
+                let attacks = match piece {
+                    Piece::Knight => KNIGHT_ATTACKS[sq],
+                    Piece::Bishop => bishop_attacks(sq, state.occupancy),
+                    Piece::Rook => rook_attacks(sq, state.occupancy),
+                    _ => queen_attacks(sq, state.occupancy)
                 };
-                mg[*colour as usize] += MG_PC_VALS[*piece as usize] + MG_SQ_VALS[*piece as usize][sq];
-                eg[*colour as usize] += EG_PC_VALS[*piece as usize] + EG_SQ_VALS[*piece as usize][sq];
-                phase += PHASE_INCREMENTS[*piece as usize];
+                let mobility = count_bits(attacks & safe) as isize;
+                let (mobility_mg, mobility_eg) = params.mobility[*piece as usize];
+                add((mobility_mg * (mobility - MOBILITY_BASELINE[*piece as usize]), mobility_eg * (mobility - MOBILITY_BASELINE[*piece as usize])));
+
+                let (file, rank) = (sq_file(sq), relative_rank(*colour, sq));
+                match piece {
+                    Piece::Knight => {
+                        // An outpost: defended by a pawn, and no enemy pawn can ever come to chase it away
+                        let defended = pawn_attacks(own_pawns, *colour) & (1 << sq) != 0;
+                        let unassailable = adjacent_files(file) & forward_ranks(*colour, sq_rank(sq)) & enemy_pawns == 0;
+                        if (3..=5).contains(&rank) && defended && unassailable {
+                            add(params.knight_outpost);
+                        }
+                    },
+                    Piece::Bishop => {
+                        // A bishop which took the pawn on a7 (or h7) and can be shut in by the pawn in front of it
+                        let (corner, blocker) = match colour {
+                            Colour::White => ([48, 55], [41, 46]),
+                            Colour::Black => ([8, 15], [17, 22])
+                        };
+                        if (sq == corner[0] && enemy_pawns & (1 << blocker[0]) != 0) || (sq == corner[1] && enemy_pawns & (1 << blocker[1]) != 0) {
+                            add(params.trapped_bishop);
+                        }
+                    },
+                    Piece::Rook => {
+                        if file_mask(file) & pawns == 0 {
+                            add(params.rook_open_file);
+                        }
+                        else if file_mask(file) & own_pawns == 0 {
+                            add(params.rook_semi_open_file);
+                        }
+
+                        // On the seventh rank it's either attacking pawns or cutting the king off on the back rank
+                        let seventh_rank = 0xff << (8 * match colour { Colour::White => 6, Colour::Black => 1 });
+                        let king_on_back_rank = matches!(enemy_king, Some(king) if relative_rank(*colour, king) == 7);
+                        if rank == 6 && (enemy_pawns & seventh_rank != 0 || king_on_back_rank) {
+                            add(params.rook_on_seventh);
+                        }
+
+                        // Hemmed in on the back rank by a king which has moved without castling, so can no longer castle on that wing to free it
+                        if let Some(king) = own_king {
+                            let king_file = sq_file(king);
+                            let boxed_in = (king_file >= 4 && file > king_file) || (king_file < 4 && file < king_file);
+                            let can_castle = decode_castling(state.castling, match (*colour, file > king_file) {
+                                (Colour::White, true) => CastleType::WhiteKingside,
+                                (Colour::White, false) => CastleType::WhiteQueenside,
+                                (Colour::Black, true) => CastleType::BlackKingside,
+                                (Colour::Black, false) => CastleType::BlackQueenside
+                            });
+                            if rank == 0 && relative_rank(*colour, king) == 0 && boxed_in && !can_castle && mobility <= 3 {
+                                add(params.trapped_rook);
+                            }
+                        }
+                    },
+                    _ => {}
+                }
             }
         }
+
+        mg += sign * colour_mg;
+        eg += sign * colour_eg;
+    }
+
+    (mg, eg)
+}
+
+// From White's point of view
+pub fn eval(state: &State) -> isize {
+    match state.to_move {
+        Colour::White => relative_eval(state),
+        Colour::Black => -relative_eval(state)
     }
+}
+
+pub fn classical_eval(state: &State) -> isize {
+    debug_assert_eq!((state.mg_score, state.eg_score, state.phase), material_and_psts(state, &PARAMS));
+
+    taper(state, &PARAMS, (state.mg_score, state.eg_score), state.phase, evaluate_pawns(state))
+}
+
+// Works everything out from scratch with the given parameters, rather than relying on the state and the pawn hash, for the tuner
+pub fn classical_eval_with(state: &State, params: &EvalParams) -> isize {
+    let (mg_score, eg_score, phase) = material_and_psts(state, params);
+
+    taper(state, params, (mg_score, eg_score), phase, evaluate_pawns_with(state, params))
+}
 
-    let mg_score = mg[Colour::White as usize] - mg[Colour::Black as usize];
-    let eg_score = eg[Colour::White as usize] - eg[Colour::Black as usize];
+// Adds the remaining terms to the material, piece-square and pawn scores, and blends the midgame and endgame totals by phase
+fn taper(state: &State, params: &EvalParams, (mg_score, eg_score): (isize, isize), phase: isize, (pawns_mg, pawns_eg): (isize, isize)) -> isize {
+    let (pieces_mg, pieces_eg) = evaluate_pieces(state, params);
+    let (king_mg, king_eg) = evaluate_king_safety(state, params);
+    let (tempo_mg, tempo_eg) = match state.to_move {
+        Colour::White => params.tempo,
+        Colour::Black => (-params.tempo.0, -params.tempo.1)
+    };
+    let mg_score = mg_score + pawns_mg + pieces_mg + king_mg + tempo_mg;
+    let eg_score = eg_score + pawns_eg + pieces_eg + king_eg + tempo_eg;
 
-    let mg_phase = min(24, phase);
-    let eg_phase = 24 - mg_phase;
+    let max_phase = params.max_phase();
+    let mg_phase = min(max_phase, phase);
+    let eg_phase = max_phase - mg_phase;
 
-    (mg_score * mg_phase + eg_score * eg_phase) / 24
+    (mg_score * mg_phase + eg_score * eg_phase) / max_phase
 }
 
+// From the point of view of the side to move
 pub fn relative_eval(state: &State) -> isize {
     match state.to_move {
-        Colour::White => eval(&state),
-        Colour::Black => -1 * eval(&state)
+        Colour::White => classical_eval(state),
+        Colour::Black => -classical_eval(state)
     }
 }
\ No newline at end of file
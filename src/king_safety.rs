@@ -0,0 +1,122 @@
+use crate::bitboards::{pop_ls1b, count_bits, get_ls1b};
+use crate::colours::Colour;
+use crate::pieces::Piece;
+use crate::state::State;
+use crate::attacks::{KNIGHT_ATTACKS, KING_ATTACKS, bishop_attacks, rook_attacks, queen_attacks};
+use crate::helpers::{sq_file, sq_rank};
+use crate::pawns::{pawn_attacks, file_mask, relative_rank};
+use crate::params::EvalParams;
+use lazy_static::lazy_static;
+use std::cmp::{max, min};
+
+const MAX_DANGER: isize = 500;
+
+lazy_static! {
+    // Grows quadratically with the attack units, so that attacks on a king which is already in trouble count for more
+    static ref KING_DANGER: [isize; 100] = {
+        let mut table = [0; 100];
+        for (units, danger) in table.iter_mut().enumerate() {
+            *danger = min((units * units) as isize / 2, MAX_DANGER);
+        }
+        table
+    };
+}
+
+fn piece_attacks(piece: Piece, sq: usize, occupancy: u64) -> u64 {
+    match piece {
+        Piece::Knight => KNIGHT_ATTACKS[sq],
+        Piece::Bishop => bishop_attacks(sq, occupancy),
+        Piece::Rook => rook_attacks(sq, occupancy),
+        Piece::Queen => queen_attacks(sq, occupancy),
+        _ => 0
+    }
+}
+
+// Every square attacked by one side
+fn attacked_squares(state: &State, colour: Colour) -> u64 {
+    let own = state.colours[colour as usize];
+    let mut attacked = pawn_attacks(state.pieces[Piece::Pawn as usize] & own, colour);
+    if let Some(king) = get_ls1b(state.pieces[Piece::King as usize] & own) {
+        attacked |= KING_ATTACKS[king];
+    }
+    for piece in &[Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let mut bb = state.pieces[*piece as usize] & own;
+        while bb != 0 {
+            attacked |= piece_attacks(*piece, pop_ls1b(&mut bb), state.occupancy);
+        }
+    }
+
+    attacked
+}
+
+// The pawn shield, pawn storms and open files around one side's king, and how heavily the enemy pieces are attacking it
+fn king_safety(state: &State, params: &EvalParams, colour: Colour) -> (isize, isize) {
+    let king = match get_ls1b(state.pieces[Piece::King as usize] & state.colours[colour as usize]) {
+        Some(king) => king,
+        None => {
+            return (0, 0);
+        }
+    };
+    let own_pawns = state.pieces[Piece::Pawn as usize] & state.colours[colour as usize];
+    let enemy_pawns = state.pieces[Piece::Pawn as usize] & state.colours[!colour as usize];
+    let mut mg = 0;
+
+    let king_file = sq_file(king);
+    for file in max(king_file, 1) - 1..=min(king_file + 1, 7) {
+        // The shield only matters once the king has settled on its back two ranks
+        if relative_rank(colour, king) <= 1 {
+            let shield = (1..=2).find(|&distance| {
+                let rank = match colour {
+                    Colour::White => sq_rank(king) + distance,
+                    Colour::Black => sq_rank(king) - distance
+                };
+                own_pawns & (1 << (rank * 8 + file)) != 0
+            });
+            mg += params.pawn_shield[shield.map_or(2, |distance| distance - 1)];
+        }
+
+        let mut storming = enemy_pawns & file_mask(file);
+        while storming != 0 {
+            mg += params.pawn_storm[relative_rank(colour, pop_ls1b(&mut storming))];
+        }
+
+        if file_mask(file) & (own_pawns | enemy_pawns) == 0 {
+            mg += params.king_open_file;
+        }
+        else if file_mask(file) & own_pawns == 0 {
+            mg += params.king_semi_open_file;
+        }
+    }
+
+    // Add up the attack units of every enemy piece hitting the squares around the king
+    let zone = KING_ATTACKS[king] | (1 << king);
+    let defended = attacked_squares(state, colour);
+    let safe = !defended & !state.colours[!colour as usize];
+    let mut attackers = 0;
+    let mut units = 0;
+    for piece in &[Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let checks = piece_attacks(*piece, king, state.occupancy);
+        let mut bb = state.pieces[*piece as usize] & state.colours[!colour as usize];
+        while bb != 0 {
+            let attacks = piece_attacks(*piece, pop_ls1b(&mut bb), state.occupancy);
+            if attacks & zone != 0 {
+                attackers += 1;
+                units += params.attack_units[*piece as usize] * count_bits(attacks & zone) as isize;
+            }
+            if attacks & checks & safe != 0 {
+                units += params.safe_check_units[*piece as usize];
+            }
+        }
+    }
+    let danger = KING_DANGER[units.clamp(0, 99) as usize] * params.attacker_weights[min(attackers, 7)] / 100;
+
+    (mg - danger, -danger / 8)
+}
+
+// From White's point of view
+pub fn evaluate_king_safety(state: &State, params: &EvalParams) -> (isize, isize) {
+    let (white_mg, white_eg) = king_safety(state, params, Colour::White);
+    let (black_mg, black_eg) = king_safety(state, params, Colour::Black);
+
+    (white_mg - black_mg, white_eg - black_eg)
+}
\ No newline at end of file
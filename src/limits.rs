@@ -0,0 +1,33 @@
+use crate::moves::BitMove;
+use std::time::Duration;
+
+#[derive(Clone)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<usize>,
+    pub mate: Option<usize>, // in moves, not plies
+    pub movetime: Option<Duration>,
+    pub times: [Option<Duration>; 2],
+    pub increments: [Option<Duration>; 2],
+    pub movestogo: Option<usize>,
+    pub infinite: bool,
+    pub ponder: bool,
+    pub searchmoves: Vec<BitMove>
+}
+
+impl SearchLimits {
+    pub fn new() -> Self {
+        Self {
+            depth: None,
+            nodes: None,
+            mate: None,
+            movetime: None,
+            times: [None; 2],
+            increments: [None; 2],
+            movestogo: None,
+            infinite: false,
+            ponder: false,
+            searchmoves: Vec::new()
+        }
+    }
+}
\ No newline at end of file
@@ -14,11 +14,22 @@ mod search;
 mod tests;
 mod zobrist;
 mod book;
+mod options;
+mod limits;
+mod tt;
+mod time;
+mod ordering;
+mod nnue;
+mod pawns;
+mod king_safety;
+mod params;
+mod default_params;
+mod tuner;
 use text_io::read;
 use std::sync::{Arc, Mutex};
 use std::env;
+use std::thread;
 use getopts::Options;
-use lazy_static;
 
 fn main() {
     lazy_static::initialize(&book::BOOK);
@@ -26,11 +37,25 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     let mut opts = Options::new();
     opts.optopt("t", "syzygy", "Tablebase directory", "Tablebase");
-    let tb_directory = match opts.parse(&args[1..]) {
-        Ok(matches) => matches.opt_str("t"),
+    opts.optopt("", "tune", "Tune the evaluation on a file of positions labelled with results, then exit", "FILE");
+    opts.optopt("", "output", "Where the tuner writes the parameters it finds, as Rust source", "FILE");
+    opts.optopt("", "threads", "How many threads the tuner uses", "N");
+    let matches = match opts.parse(&args[1..]) {
+        Ok(matches) => matches,
         Err(_) => panic!()
     };
 
+    if let Some(path) = matches.opt_str("tune") {
+        let output = matches.opt_str("output").unwrap_or_else(|| "default_params.rs".to_string());
+        let threads = matches.opt_str("threads").and_then(|n| n.parse().ok()).unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+        if let Err(e) = tuner::tune(&path, &output, threads) {
+            eprintln!("Tuning failed: {}", e);
+        }
+        return;
+    }
+
+    let tb_directory = matches.opt_str("t");
+
     let mut uci = uci::UciHandler::new(tb_directory, Arc::new(Mutex::new(std::io::stdout())));
 
     loop {
@@ -9,6 +9,7 @@ use regex::Regex;
 
 pub type BitMove = u32;
 
+#[allow(clippy::too_many_arguments)]
 pub fn encode_move(from: usize, to: usize, piece: Piece, promotion_piece: Option<Piece>, is_capture: bool, is_double_push: bool, is_ep: bool, is_castle: bool) -> BitMove {
     let mut encoded = 0u32;
 
@@ -0,0 +1,134 @@
+use crate::colours::Colour;
+use crate::pieces::Piece;
+use crate::errors::InvalidNetworkError;
+use crate::state::State;
+use crate::bitboards::pop_ls1b;
+use std::fs;
+
+// A 768 -> HIDDEN_SIZE x 2 -> 1 network, with one accumulator from each side's perspective and a squared clipped ReLU activation.
+// The weights file is a flat list of little-endian i16s in the order they're declared below, as written by bullet's simple layout
+pub const HIDDEN_SIZE: usize = 128;
+const NUM_FEATURES: usize = 768;
+// Weights are quantised by these factors, and the output scaled from the network's units to centipawns
+const QA: i64 = 255;
+const QB: i64 = 64;
+const SCALE: i64 = 400;
+
+pub struct Network {
+    feature_weights: Vec<[i16; HIDDEN_SIZE]>,
+    feature_bias: [i16; HIDDEN_SIZE],
+    output_weights: [i16; 2 * HIDDEN_SIZE],
+    output_bias: i16
+}
+
+pub type Accumulator = [i16; HIDDEN_SIZE];
+
+impl Network {
+    pub fn from_file(path: &str) -> Result<Self, InvalidNetworkError> {
+        match fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(_) => None
+        }.ok_or(InvalidNetworkError {
+            path: path.to_string()
+        })
+    }
+
+    // Trainers pad the file out to a multiple of 64 bytes, so a little left over at the end is allowed for
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let expected_len = 2 * (NUM_FEATURES * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1);
+        if bytes.len() < expected_len || bytes.len() >= expected_len + 64 {
+            return None;
+        }
+
+        let mut values = bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+        let mut network = Self {
+            feature_weights: vec![[0; HIDDEN_SIZE]; NUM_FEATURES],
+            feature_bias: [0; HIDDEN_SIZE],
+            output_weights: [0; 2 * HIDDEN_SIZE],
+            output_bias: 0
+        };
+        for weights in network.feature_weights.iter_mut() {
+            weights.iter_mut().for_each(|w| *w = values.next().unwrap());
+        }
+        network.feature_bias.iter_mut().for_each(|b| *b = values.next().unwrap());
+        network.output_weights.iter_mut().for_each(|w| *w = values.next().unwrap());
+        network.output_bias = values.next().unwrap();
+
+        Some(network)
+    }
+
+    // The accumulators for a position, built from scratch
+    pub fn accumulators(&self, state: &State) -> [Accumulator; 2] {
+        let mut accumulators = [self.feature_bias; 2];
+        for colour in &[Colour::White, Colour::Black] {
+            for piece in &[Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+                let mut bb = state.pieces[*piece as usize] & state.colours[*colour as usize];
+                while bb != 0 {
+                    self.add_feature(&mut accumulators, *colour, *piece, pop_ls1b(&mut bb));
+                }
+            }
+        }
+        accumulators
+    }
+
+    // Brings the accumulators for the position before a move up to date with the position after it, by way of whichever pieces have come or gone
+    pub fn update_accumulators(&self, accumulators: &mut [Accumulator; 2], before: &State, after: &State) {
+        for colour in &[Colour::White, Colour::Black] {
+            for piece in &[Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+                let old = before.pieces[*piece as usize] & before.colours[*colour as usize];
+                let new = after.pieces[*piece as usize] & after.colours[*colour as usize];
+                let (mut removed, mut added) = (old & !new, new & !old);
+                while removed != 0 {
+                    self.remove_feature(accumulators, *colour, *piece, pop_ls1b(&mut removed));
+                }
+                while added != 0 {
+                    self.add_feature(accumulators, *colour, *piece, pop_ls1b(&mut added));
+                }
+            }
+        }
+    }
+
+    fn add_feature(&self, accumulators: &mut [Accumulator; 2], colour: Colour, piece: Piece, sq: usize) {
+        for perspective in &[Colour::White, Colour::Black] {
+            let weights = &self.feature_weights[feature_index(*perspective, colour, piece, sq)];
+            for (a, w) in accumulators[*perspective as usize].iter_mut().zip(weights.iter()) {
+                *a += *w;
+            }
+        }
+    }
+
+    fn remove_feature(&self, accumulators: &mut [Accumulator; 2], colour: Colour, piece: Piece, sq: usize) {
+        for perspective in &[Colour::White, Colour::Black] {
+            let weights = &self.feature_weights[feature_index(*perspective, colour, piece, sq)];
+            for (a, w) in accumulators[*perspective as usize].iter_mut().zip(weights.iter()) {
+                *a -= *w;
+            }
+        }
+    }
+
+    // The evaluation in centipawns from the point of view of the side to move
+    pub fn evaluate(&self, accumulators: &[Accumulator; 2], to_move: Colour) -> isize {
+        let (us, them) = (&accumulators[to_move as usize], &accumulators[!to_move as usize]);
+        let mut output: i64 = 0;
+        for (&a, &w) in us.iter().chain(them.iter()).zip(self.output_weights.iter()) {
+            let activated = (a as i64).clamp(0, QA);
+            output += activated * activated * w as i64;
+        }
+
+        // The activation was squared, so one factor of QA has to come out before the bias can be added
+        ((output / QA + self.output_bias as i64) * SCALE / (QA * QB)) as isize
+    }
+
+    // As above, for a position without accumulators to hand
+    pub fn evaluate_state(&self, state: &State) -> isize {
+        self.evaluate(&self.accumulators(state), state.to_move)
+    }
+}
+
+// Pieces are seen relative to the perspective: its own pieces come first and the board is flipped for Black
+fn feature_index(perspective: Colour, colour: Colour, piece: Piece, sq: usize) -> usize {
+    match perspective {
+        Colour::White => (colour as usize) * 384 + (piece as usize) * 64 + sq,
+        Colour::Black => (!colour as usize) * 384 + (piece as usize) * 64 + (sq ^ 56)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+use crate::errors::InvalidOptionError;
+use crate::search::{RFP_MARGIN, RAZORING_MARGIN, FUTILITY_BASE_MARGIN, FUTILITY_MARGIN};
+use std::fmt;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(isize, isize, isize), // value, min, max
+    String(String),
+    Button
+}
+
+pub struct UciOption {
+    pub name: &'static str,
+    default: OptionValue,
+    value: OptionValue
+}
+
+impl UciOption {
+    fn new(name: &'static str, default: OptionValue) -> Self {
+        Self {
+            name,
+            default: default.clone(),
+            value: default
+        }
+    }
+}
+
+impl fmt::Display for UciOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.default {
+            OptionValue::Check(default) => write!(f, "option name {} type check default {}", self.name, default),
+            OptionValue::Spin(default, min, max) => write!(f, "option name {} type spin default {} min {} max {}", self.name, default, min, max),
+            OptionValue::String(default) => write!(f, "option name {} type string default {}", self.name, default),
+            OptionValue::Button => write!(f, "option name {} type button", self.name)
+        }
+    }
+}
+
+pub struct Options {
+    options: Vec<UciOption>
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            options: vec![
+                UciOption::new("Hash", OptionValue::Spin(16, 1, 65536)),
+                UciOption::new("Clear Hash", OptionValue::Button),
+                UciOption::new("Threads", OptionValue::Spin(1, 1, 256)),
+                UciOption::new("SyzygyPath", OptionValue::String("<empty>".to_string())),
+                UciOption::new("OwnBook", OptionValue::Check(true)),
+                UciOption::new("Ponder", OptionValue::Check(false)),
+                UciOption::new("MultiPV", OptionValue::Spin(1, 1, 256)),
+                UciOption::new("Move Overhead", OptionValue::Spin(10, 0, 5000)),
+                UciOption::new("EvalFile", OptionValue::String("<empty>".to_string())),
+                UciOption::new("UseNNUE", OptionValue::Check(true)),
+                UciOption::new("RFPMargin", OptionValue::Spin(RFP_MARGIN, 0, 1000)),
+                UciOption::new("RazoringMargin", OptionValue::Spin(RAZORING_MARGIN, 0, 2000)),
+                UciOption::new("FutilityBaseMargin", OptionValue::Spin(FUTILITY_BASE_MARGIN, 0, 1000)),
+                UciOption::new("FutilityMargin", OptionValue::Spin(FUTILITY_MARGIN, 0, 1000))
+            ]
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &UciOption> {
+        self.options.iter()
+    }
+
+    // Option names are matched case-insensitively, as required by the UCI protocol. On success the canonical name of the option is returned
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<&'static str, InvalidOptionError> {
+        let option = match self.options.iter_mut().find(|o| o.name.eq_ignore_ascii_case(name)) {
+            Some(option) => option,
+            None => {
+                return Err(InvalidOptionError {
+                    name: name.to_string()
+                });
+            }
+        };
+
+        option.value = match (&option.value, value) {
+            (OptionValue::Check(_), Some("true")) => OptionValue::Check(true),
+            (OptionValue::Check(_), Some("false")) => OptionValue::Check(false),
+            (OptionValue::Spin(_, min, max), Some(v)) => match v.parse::<isize>() {
+                Ok(v) => OptionValue::Spin(v.clamp(*min, *max), *min, *max),
+                Err(_) => {
+                    return Err(InvalidOptionError {
+                        name: name.to_string()
+                    });
+                }
+            },
+            (OptionValue::String(_), Some(v)) => OptionValue::String(v.to_string()),
+            (OptionValue::Button, _) => OptionValue::Button,
+            _ => {
+                return Err(InvalidOptionError {
+                    name: name.to_string()
+                });
+            }
+        };
+
+        Ok(option.name)
+    }
+
+    fn get(&self, name: &str) -> &OptionValue {
+        match self.options.iter().find(|o| o.name == name) {
+            Some(option) => &option.value,
+            None => panic!("Unknown option {}", name)
+        }
+    }
+
+    pub fn check(&self, name: &str) -> bool {
+        match self.get(name) {
+            OptionValue::Check(value) => *value,
+            _ => panic!("Option {} is not a check", name)
+        }
+    }
+
+    pub fn spin(&self, name: &str) -> isize {
+        match self.get(name) {
+            OptionValue::Spin(value, _, _) => *value,
+            _ => panic!("Option {} is not a spin", name)
+        }
+    }
+
+    // Empty strings and the conventional "<empty>" placeholder are both treated as the option being unset
+    pub fn string(&self, name: &str) -> Option<String> {
+        match self.get(name) {
+            OptionValue::String(value) if value.is_empty() || value == "<empty>" => None,
+            OptionValue::String(value) => Some(value.clone()),
+            _ => panic!("Option {} is not a string", name)
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,118 @@
+use crate::colours::Colour;
+use crate::pieces::Piece;
+use crate::moves::{BitMove, move_from, move_to, move_piece};
+
+// Every history score stays within plus or minus this, with updates having less effect the closer an entry already is to it
+pub const MAX_HISTORY: i32 = 16384;
+const MAX_BONUS: i32 = 1536;
+
+// Indexed by the previous move's piece (with its colour) and destination, then by our move's piece and destination
+type ContinuationHistory = Vec<[[[i32; 64]; 6]; 64]>;
+
+// The moves played to reach the current position, most recent first, with 0 standing for a null move or for there being no move
+pub type PreviousMoves = [BitMove; 2];
+
+// Kept from one search to the next, with Default giving an empty placeholder until a search hands over the real tables
+#[derive(Default)]
+pub struct MoveHistory {
+    butterfly: Vec<[[i32; 64]; 64]>,
+    counter_moves: Vec<[BitMove; 64]>,
+    continuations: [ContinuationHistory; 2],
+    captures: Vec<[[i32; 6]; 64]>
+}
+
+impl MoveHistory {
+    pub fn new() -> Self {
+        Self {
+            butterfly: vec![[[0; 64]; 64]; 2],
+            counter_moves: vec![[0; 64]; 12],
+            continuations: [vec![[[[0; 64]; 6]; 64]; 12], vec![[[[0; 64]; 6]; 64]; 12]],
+            captures: vec![[[0; 6]; 64]; 12]
+        }
+    }
+
+    // Called at the start of each search, so that what was learnt on earlier moves still helps but counts for less than what's learnt now
+    pub fn age(&mut self) {
+        let butterfly = self.butterfly.iter_mut().flatten().flatten();
+        let continuations = self.continuations.iter_mut().flatten().flatten().flatten().flatten();
+        let captures = self.captures.iter_mut().flatten().flatten();
+        for entry in butterfly.chain(continuations).chain(captures) {
+            *entry /= 2;
+        }
+    }
+
+    fn bonus(depth: usize) -> i32 {
+        (32 * depth * depth).min(MAX_BONUS as usize) as i32
+    }
+
+    pub fn quiet_score(&self, colour: Colour, r#move: BitMove, previous_moves: PreviousMoves) -> isize {
+        let mut score = self.butterfly[colour as usize][move_from(r#move)][move_to(r#move)] as isize;
+        for (i, &previous_move) in previous_moves.iter().enumerate() {
+            if previous_move != 0 {
+                score += self.continuations[i][piece_index(previous_colour(colour, i), previous_move)][move_to(previous_move)][move_piece(r#move) as usize][move_to(r#move)] as isize;
+            }
+        }
+        score
+    }
+
+    pub fn capture_score(&self, colour: Colour, r#move: BitMove, captured_piece: Piece) -> isize {
+        self.captures[piece_index(colour, r#move)][move_to(r#move)][captured_piece as usize] as isize
+    }
+
+    // The move which last refuted the opponent's previous move
+    pub fn counter_move(&self, colour: Colour, previous_moves: PreviousMoves) -> BitMove {
+        match previous_moves[0] {
+            0 => 0,
+            previous_move => self.counter_moves[piece_index(!colour, previous_move)][move_to(previous_move)]
+        }
+    }
+
+    // Rewards the quiet move which caused a cutoff, and penalises the ones tried before it which didn't
+    pub fn update_quiets(&mut self, colour: Colour, best_move: BitMove, tried: &[BitMove], previous_moves: PreviousMoves, depth: usize) {
+        let bonus = Self::bonus(depth);
+        self.update_quiet(colour, best_move, previous_moves, bonus);
+        for &r#move in tried {
+            self.update_quiet(colour, r#move, previous_moves, -bonus);
+        }
+
+        if previous_moves[0] != 0 {
+            self.counter_moves[piece_index(!colour, previous_moves[0])][move_to(previous_moves[0])] = best_move;
+        }
+    }
+
+    // As for quiets, but each capture comes with the piece it captures
+    pub fn update_captures(&mut self, colour: Colour, best_move: (BitMove, Piece), tried: &[(BitMove, Piece)], depth: usize) {
+        let bonus = Self::bonus(depth);
+        self.update_capture(colour, best_move, bonus);
+        for &capture in tried {
+            self.update_capture(colour, capture, -bonus);
+        }
+    }
+
+    fn update_quiet(&mut self, colour: Colour, r#move: BitMove, previous_moves: PreviousMoves, bonus: i32) {
+        apply_bonus(&mut self.butterfly[colour as usize][move_from(r#move)][move_to(r#move)], bonus);
+        for (i, &previous_move) in previous_moves.iter().enumerate() {
+            if previous_move != 0 {
+                apply_bonus(&mut self.continuations[i][piece_index(previous_colour(colour, i), previous_move)][move_to(previous_move)][move_piece(r#move) as usize][move_to(r#move)], bonus);
+            }
+        }
+    }
+
+    fn update_capture(&mut self, colour: Colour, (r#move, captured_piece): (BitMove, Piece), bonus: i32) {
+        apply_bonus(&mut self.captures[piece_index(colour, r#move)][move_to(r#move)][captured_piece as usize], bonus);
+    }
+}
+
+// Gravity: the bonus is scaled down as the entry approaches the limit, so that it can never go past it and old results gradually fade out
+fn apply_bonus(entry: &mut i32, bonus: i32) {
+    *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+}
+
+fn piece_index(colour: Colour, r#move: BitMove) -> usize {
+    colour as usize * 6 + move_piece(r#move) as usize
+}
+
+// The move i + 1 plies ago was played by the opponent if i is even, or by us if it's odd
+fn previous_colour(colour: Colour, i: usize) -> Colour {
+    if i.is_multiple_of(2) { !colour } else { colour }
+}
\ No newline at end of file
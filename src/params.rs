@@ -0,0 +1,158 @@
+use std::fmt;
+
+// Anything which can be one of the evaluation parameters: a single weight, a midgame and endgame pair, or an array of them
+pub trait Param {
+    fn values_mut<'a>(&'a mut self, values: &mut Vec<&'a mut isize>);
+    // Rust source for the value, with any lines after the first indented by the given amount
+    fn source(&self, indent: usize) -> String;
+}
+
+impl Param for isize {
+    fn values_mut<'a>(&'a mut self, values: &mut Vec<&'a mut isize>) {
+        values.push(self);
+    }
+
+    fn source(&self, _indent: usize) -> String {
+        self.to_string()
+    }
+}
+
+impl Param for (isize, isize) {
+    fn values_mut<'a>(&'a mut self, values: &mut Vec<&'a mut isize>) {
+        values.push(&mut self.0);
+        values.push(&mut self.1);
+    }
+
+    fn source(&self, _indent: usize) -> String {
+        format!("({}, {})", self.0, self.1)
+    }
+}
+
+impl<T: Param, const N: usize> Param for [T; N] {
+    fn values_mut<'a>(&'a mut self, values: &mut Vec<&'a mut isize>) {
+        for value in self.iter_mut() {
+            value.values_mut(values);
+        }
+    }
+
+    // Short arrays go on one line, piece-square tables get a line per rank and arrays of arrays a line per array
+    fn source(&self, indent: usize) -> String {
+        let inner = " ".repeat(indent + 4);
+        let values: Vec<String> = self.iter().map(|value| value.source(indent + 4)).collect();
+        if N == 64 {
+            let ranks: Vec<String> = values.chunks(8).map(|rank| format!("{}{},", inner, rank.join(", "))).collect();
+            format!("[\n{}\n{}]", ranks.join("\n"), " ".repeat(indent))
+        }
+        else if values.iter().any(|value| value.contains('\n')) {
+            let lines: Vec<String> = values.iter().map(|value| format!("{}{},", inner, value)).collect();
+            format!("[\n{}\n{}]", lines.join("\n"), " ".repeat(indent))
+        }
+        else {
+            format!("[{}]", values.join(", "))
+        }
+    }
+}
+
+// Declares the parameters once, so that the tuner can get at every weight and write them all back out as Rust source
+macro_rules! eval_params {
+    ($($name:ident: $type:ty),* $(,)?) => {
+        #[derive(Clone)]
+        pub struct EvalParams {
+            $(pub $name: $type),*
+        }
+
+        impl EvalParams {
+            pub fn values_mut(&mut self) -> Vec<&mut isize> {
+                let mut values = Vec::new();
+                $(self.$name.values_mut(&mut values);)*
+                values
+            }
+        }
+
+        // The whole of a source file defining the parameters as the ones the engine uses
+        impl fmt::Display for EvalParams {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                writeln!(f, "// Generated by the tuner (silverfish --tune), which writes the parameters it finds out in this form")?;
+                writeln!(f, "use crate::params::EvalParams;")?;
+                writeln!(f)?;
+                writeln!(f, "pub static PARAMS: EvalParams = EvalParams {{")?;
+                $(writeln!(f, "    {}: {},", stringify!($name), self.$name.source(4))?;)*
+                write!(f, "}};")
+            }
+        }
+    };
+}
+
+eval_params! {
+    // Material and piece-square tables, the tables laid out as the board looks from White's side with the eighth rank first
+    mg_pc_vals: [isize; 6],
+    eg_pc_vals: [isize; 6],
+    mg_sq_vals: [[isize; 64]; 6],
+    eg_sq_vals: [[isize; 64]; 6],
+    phase_increments: [isize; 6],
+    // Pieces
+    mobility: [(isize, isize); 6],
+    bishop_pair: (isize, isize),
+    rook_open_file: (isize, isize),
+    rook_semi_open_file: (isize, isize),
+    rook_on_seventh: (isize, isize),
+    knight_outpost: (isize, isize),
+    trapped_bishop: (isize, isize),
+    trapped_rook: (isize, isize),
+    tempo: (isize, isize),
+    // Pawn structure, indexed by rank from the pawn's own side of the board
+    passed_mg: [isize; 8],
+    passed_eg: [isize; 8],
+    connected: [isize; 8],
+    candidate_mg: [isize; 8],
+    candidate_eg: [isize; 8],
+    king_proximity: [isize; 8],
+    isolated: (isize, isize),
+    doubled: (isize, isize),
+    backward: (isize, isize),
+    // King safety
+    pawn_shield: [isize; 3],
+    pawn_storm: [isize; 8],
+    king_semi_open_file: isize,
+    king_open_file: isize,
+    attack_units: [isize; 6],
+    safe_check_units: [isize; 6],
+    attacker_weights: [isize; 8]
+}
+
+impl EvalParams {
+    // The phase of the starting position, which counts as pure midgame
+    pub fn max_phase(&self) -> isize {
+        [16, 4, 4, 4, 2, 2].iter().zip(self.phase_increments.iter()).map(|(count, increment)| count * increment).sum()
+    }
+
+    // Which of the values, in the order of values_mut, can change the evaluation at all, so that the tuner doesn't waste passes on the rest
+    pub fn tunable(&self) -> Vec<bool> {
+        let mut mask = self.clone();
+        for value in mask.values_mut() {
+            *value = 1;
+        }
+
+        // Kings are always on the board, and only knights, bishops, rooks and queens are counted for mobility and attacks on the king
+        mask.mg_pc_vals[5] = 0;
+        mask.eg_pc_vals[5] = 0;
+        for piece in &[0, 5] {
+            mask.mobility[*piece] = (0, 0);
+            mask.attack_units[*piece] = 0;
+            mask.safe_check_units[*piece] = 0;
+        }
+
+        // Pawns can never stand on the first or eighth rank
+        for sq in (0..8).chain(56..64) {
+            mask.mg_sq_vals[0][sq] = 0;
+            mask.eg_sq_vals[0][sq] = 0;
+        }
+        for rank in &[0, 7] {
+            for table in &mut [&mut mask.passed_mg, &mut mask.passed_eg, &mut mask.connected, &mut mask.candidate_mg, &mut mask.candidate_eg, &mut mask.king_proximity, &mut mask.pawn_storm] {
+                table[*rank] = 0;
+            }
+        }
+
+        mask.values_mut().into_iter().map(|value| *value != 0).collect()
+    }
+}
\ No newline at end of file
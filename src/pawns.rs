@@ -0,0 +1,216 @@
+use crate::bitboards::{pop_ls1b, count_bits, get_ls1b};
+use crate::colours::Colour;
+use crate::pieces::Piece;
+use crate::state::State;
+use crate::attacks::PAWN_ATTACKS;
+use crate::helpers::{sq_rank, sq_file};
+use crate::params::EvalParams;
+use crate::default_params::PARAMS;
+use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::cmp::max;
+use std::sync::Mutex;
+
+// A power of two, so that the index can be taken by masking
+const PAWN_HASH_SIZE: usize = 16384;
+
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+    key: u64,
+    mg: isize,
+    eg: isize,
+    passed: [u64; 2]
+}
+
+lazy_static! {
+    // Tables handed back by threads which have finished, so that the threads of the next search start out with them warm
+    static ref SPARE_TABLES: Mutex<Vec<Vec<PawnEntry>>> = Mutex::new(Vec::new());
+}
+
+struct PawnTable(Vec<PawnEntry>);
+
+impl PawnTable {
+    fn new() -> Self {
+        Self(SPARE_TABLES.lock().unwrap().pop().unwrap_or_else(|| vec![PawnEntry::default(); PAWN_HASH_SIZE]))
+    }
+}
+
+impl Drop for PawnTable {
+    fn drop(&mut self) {
+        if let Ok(mut spare_tables) = SPARE_TABLES.lock() {
+            spare_tables.push(std::mem::take(&mut self.0));
+        }
+    }
+}
+
+// Each search thread gets a table of its own, so there's no need for any synchronisation
+thread_local! {
+    static PAWN_HASH: RefCell<PawnTable> = RefCell::new(PawnTable::new());
+}
+
+pub fn file_mask(file: usize) -> u64 {
+    0x0101010101010101 << file
+}
+
+pub fn adjacent_files(file: usize) -> u64 {
+    (if file > 0 { file_mask(file - 1) } else { 0 }) | (if file < 7 { file_mask(file + 1) } else { 0 })
+}
+
+// Every square on the ranks in front of the given rank, from the given side's point of view
+pub fn forward_ranks(colour: Colour, rank: usize) -> u64 {
+    match colour {
+        Colour::White if rank == 7 => 0,
+        Colour::White => u64::MAX << (8 * (rank + 1)),
+        Colour::Black => (1 << (8 * rank)) - 1
+    }
+}
+
+// Every square attacked by the given pawns
+pub fn pawn_attacks(pawns: u64, colour: Colour) -> u64 {
+    let (not_a_file, not_h_file) = (!file_mask(0), !file_mask(7));
+    match colour {
+        Colour::White => ((pawns << 7) & not_h_file) | ((pawns << 9) & not_a_file),
+        Colour::Black => ((pawns >> 9) & not_h_file) | ((pawns >> 7) & not_a_file)
+    }
+}
+
+// No enemy pawn stands in front of it or on either neighbouring file to stop it from promoting
+pub fn is_passed(state: &State, sq: usize, colour: Colour) -> bool {
+    let enemy_pawns = state.pieces[Piece::Pawn as usize] & state.colours[!colour as usize];
+    forward_ranks(colour, sq_rank(sq)) & (file_mask(sq_file(sq)) | adjacent_files(sq_file(sq))) & enemy_pawns == 0
+}
+
+pub fn relative_rank(colour: Colour, sq: usize) -> usize {
+    match colour {
+        Colour::White => sq_rank(sq),
+        Colour::Black => 7 - sq_rank(sq)
+    }
+}
+
+fn distance(a: usize, b: usize) -> isize {
+    max((sq_rank(a) as isize - sq_rank(b) as isize).abs(), (sq_file(a) as isize - sq_file(b) as isize).abs())
+}
+
+// The terms which depend only on where the pawns are, from White's point of view, along with each side's passed pawns
+fn pawn_structure(state: &State, params: &EvalParams) -> PawnEntry {
+    let mut entry = PawnEntry {
+        key: state.pawn_hash,
+        ..Default::default()
+    };
+
+    for colour in &[Colour::White, Colour::Black] {
+        let sign = match colour {
+            Colour::White => 1,
+            Colour::Black => -1
+        };
+        let own = state.pieces[Piece::Pawn as usize] & state.colours[*colour as usize];
+        let enemy = state.pieces[Piece::Pawn as usize] & state.colours[!*colour as usize];
+
+        let mut bb = own;
+        while bb != 0 {
+            let sq = pop_ls1b(&mut bb);
+            let (file, rank) = (sq_file(sq), relative_rank(*colour, sq));
+            let ahead = forward_ranks(*colour, sq_rank(sq));
+            let level_or_behind = !ahead;
+            let (mut mg, mut eg) = (0, 0);
+
+            let doubled = ahead & file_mask(file) & own != 0;
+            let isolated = adjacent_files(file) & own == 0;
+            let supported = PAWN_ATTACKS[sq][!*colour as usize] & own != 0;
+            let phalanx = adjacent_files(file) & (0xff << (8 * sq_rank(sq))) & own != 0;
+            let passed = !doubled && ahead & (file_mask(file) | adjacent_files(file)) & enemy == 0;
+
+            if passed {
+                entry.passed[*colour as usize] |= 1 << sq;
+                mg += params.passed_mg[rank];
+                eg += params.passed_eg[rank];
+            }
+            if doubled {
+                mg += params.doubled.0;
+                eg += params.doubled.1;
+            }
+            if isolated {
+                mg += params.isolated.0;
+                eg += params.isolated.1;
+            }
+            if supported || phalanx {
+                mg += params.connected[rank];
+                eg += params.connected[rank];
+            }
+
+            // Backward: nothing on the neighbouring files can come up to support it, and it can't safely advance either
+            let stop_sq = match colour {
+                Colour::White => sq + 8,
+                Colour::Black => sq - 8
+            };
+            if !isolated && !supported && !phalanx && adjacent_files(file) & level_or_behind & own == 0 && PAWN_ATTACKS[stop_sq][*colour as usize] & enemy != 0 {
+                mg += params.backward.0;
+                eg += params.backward.1;
+            }
+
+            // Candidate: on a half-open file, with at least as many pawns to help it through as there are enemy pawns in the way
+            if !passed && !doubled && ahead & file_mask(file) & enemy == 0 {
+                let helpers = count_bits(adjacent_files(file) & level_or_behind & own);
+                let sentries = count_bits(adjacent_files(file) & ahead & enemy);
+                if helpers >= sentries {
+                    mg += params.candidate_mg[rank];
+                    eg += params.candidate_eg[rank];
+                }
+            }
+
+            entry.mg += sign * mg;
+            entry.eg += sign * eg;
+        }
+    }
+
+    entry
+}
+
+// The midgame and endgame pawn structure scores from White's point of view, with the structure looked up in the pawn hash
+pub fn evaluate_pawns(state: &State) -> (isize, isize) {
+    let entry = PAWN_HASH.with(|table| {
+        let mut table = table.borrow_mut();
+        let slot = &mut table.0[state.pawn_hash as usize & (PAWN_HASH_SIZE - 1)];
+        if slot.key != state.pawn_hash {
+            *slot = pawn_structure(state, &PARAMS);
+        }
+        *slot
+    });
+
+    (entry.mg, entry.eg + passed_pawn_kings(state, &PARAMS, entry.passed))
+}
+
+// As above, but with the given parameters and so without the pawn hash, whose entries are only valid for the engine's own
+pub fn evaluate_pawns_with(state: &State, params: &EvalParams) -> (isize, isize) {
+    let entry = pawn_structure(state, params);
+
+    (entry.mg, entry.eg + passed_pawn_kings(state, params, entry.passed))
+}
+
+// The kings move around too much to be cached, so how close they are to the passed pawns is worked out every time
+fn passed_pawn_kings(state: &State, params: &EvalParams, passed: [u64; 2]) -> isize {
+    let mut eg = 0;
+    let king_sqs = [
+        get_ls1b(state.pieces[Piece::King as usize] & state.colours[Colour::White as usize]),
+        get_ls1b(state.pieces[Piece::King as usize] & state.colours[Colour::Black as usize])
+    ];
+    if let [Some(white_king), Some(black_king)] = king_sqs {
+        for colour in &[Colour::White, Colour::Black] {
+            let (own_king, enemy_king, sign) = match colour {
+                Colour::White => (white_king, black_king, 1),
+                Colour::Black => (black_king, white_king, -1)
+            };
+            let mut passed = passed[*colour as usize];
+            while passed != 0 {
+                let sq = pop_ls1b(&mut passed);
+                let stop_sq = match colour {
+                    Colour::White => sq + 8,
+                    Colour::Black => sq - 8
+                };
+                eg += sign * params.king_proximity[relative_rank(*colour, sq)] * (5 * distance(enemy_king, stop_sq) - 2 * distance(own_king, stop_sq));
+            }
+        }
+    }
+
+    eg
+}
\ No newline at end of file
@@ -7,9 +7,9 @@ pub fn perft(state: &mut State, depth: u8) -> u64 {
     }
 
     let mut count = 0u64;
-    let mut moves = generate_moves(&state);
-    while let Some(r#move) = moves.next() {
-        let copy = state.clone();
+    let moves = generate_moves(state);
+    for r#move in moves {
+        let copy = *state;
         if state.make_move(r#move).is_err() {
             continue;
         }
@@ -1,27 +1,189 @@
-use crate::state::State;
+use crate::state::{State, SEE_VALUES};
 use crate::colours::Colour;
 use crate::pieces::Piece;
-use crate::bitboards::{get_bit, count_bits};
+use crate::bitboards::count_bits;
 use crate::eval::relative_eval;
-use crate::moves::{generate_moves, BitMove, move_is_capture, move_is_ep, move_piece, move_from, move_to, MoveList, move_to_algebraic, encode_move};
+use crate::pawns::is_passed;
+use crate::moves::{generate_moves, BitMove, move_is_capture, move_is_ep, move_piece, move_to, move_promotion_piece, MoveList, move_to_algebraic, encode_move};
 use crate::book::BOOK;
+use crate::limits::SearchLimits;
+use crate::tt::TranspositionTable;
+use crate::time::TimeManager;
+use crate::ordering::{MoveHistory, PreviousMoves, MAX_HISTORY};
+use crate::nnue::{Network, Accumulator, HIDDEN_SIZE};
 use rand::{thread_rng, Rng};
-use std::cmp::{max, min};
-use std::collections::HashMap;
+use lazy_static::lazy_static;
+use std::cmp::{max, min, Reverse};
 use std::fmt;
 use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
 use std::sync::mpsc::{Sender, Receiver};
 use shakmaty::{CastlingMode, Chess, Role};
 use shakmaty::fen::Fen;
 use shakmaty_syzygy::{Tablebase, Dtz};
 
 const MATE_VALUE: isize = 10000;
+const INFINITY: isize = MATE_VALUE + 1;
 const MAX_PLY: usize = 64;
+// Mate scores count down from MATE_VALUE by the number of plies from the root, so anything beyond this is a forced mate
+const MATE_BOUND: isize = MATE_VALUE - MAX_PLY as isize;
+const TB_WIN_VALUE: isize = MATE_BOUND - 1;
+
+// Helper threads skip some depths so that they spread out over the iterations rather than all searching the same one in lockstep
+const SKIP_SIZE: [usize; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [usize; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+// Null move pruning is only tried this deep, and above the verification depth a cutoff has to be confirmed by a normal search
+const NMP_MIN_DEPTH: usize = 3;
+const NMP_VERIFICATION_DEPTH: usize = 12;
+// The null move search is reduced by one more ply for every this much the static eval is above beta, up to NMP_MAX_EVAL_REDUCTION
+const NMP_EVAL_DIVISOR: usize = 200;
+const NMP_MAX_EVAL_REDUCTION: usize = 3;
+// Margins, per ply of depth remaining, by which the static eval has to clear the window to prune at shallow depths.
+// The margins are only defaults, since they can be tuned through UCI options
+const RFP_MAX_DEPTH: usize = 7;
+pub const RFP_MARGIN: isize = 80;
+const RAZORING_MAX_DEPTH: usize = 2;
+pub const RAZORING_MARGIN: isize = 300;
+const FUTILITY_MAX_DEPTH: usize = 4;
+pub const FUTILITY_BASE_MARGIN: isize = 100;
+pub const FUTILITY_MARGIN: isize = 80;
+
+// Quiet moves late in the ordering are searched at reduced depth, by more the deeper we are and the later the move
+const LMR_MIN_DEPTH: usize = 3;
+const LMR_HISTORY_DIVISOR: isize = 8192;
+// Iterations from this depth on start with a window this wide either side of the previous score
+const ASPIRATION_MIN_DEPTH: usize = 4;
+const ASPIRATION_WINDOW: isize = 25;
+// At shallow depths, quiet moves beyond the first few aren't searched at all
+const LMP_MAX_DEPTH: usize = 3;
+// Quiet moves which lose more than this much material per ply of depth remaining aren't searched at shallow depths
+const SEE_QUIET_MAX_DEPTH: usize = 6;
+const SEE_QUIET_MARGIN: isize = 60;
+// A TT move is singular, and extended, when every other move fails low against a margin below its score in a reduced depth search
+const SINGULAR_MIN_DEPTH: usize = 8;
+const SINGULAR_MARGIN: isize = 2;
+// No line can be extended by more than this many plies in total, nor past twice the depth of the iteration, so that extensions can't run away
+pub const MAX_PATH_EXTENSIONS: usize = 16;
+// A capture is skipped in quiescence if even this much on top of the material it wins can't raise the score to alpha
+const DELTA_MARGIN: isize = 200;
+// Without a TT move to try first a node is likely to be searched badly anyway, so we save time by searching it a ply shallower
+const IIR_MIN_DEPTH: usize = 4;
+// ProbCut: a capture which beats beta by this margin in a search this much shallower will very likely beat beta in a full search too
+const PROBCUT_MIN_DEPTH: usize = 5;
+const PROBCUT_MARGIN: isize = 200;
+const PROBCUT_REDUCTION: usize = 4;
+
+lazy_static! {
+    static ref LMR_TABLE: [[usize; 64]; 64] = {
+        let mut table = [[0; 64]; 64];
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+                *reduction = (0.75 + (depth as f64).ln() * (move_number as f64).ln() / 2.25) as usize;
+            }
+        }
+        table
+    };
+}
+
+// Converts a score into the number of moves until mate, which is positive if the side to move is delivering mate and negative if they are being mated
+pub fn mate_in(score: isize) -> Option<isize> {
+    if score >= MATE_BOUND {
+        Some((MATE_VALUE - score + 1) / 2)
+    }
+    else if score <= -MATE_BOUND {
+        Some(-(MATE_VALUE + score) / 2)
+    }
+    else {
+        None
+    }
+}
+
+// Whether a move is worth searching a ply deeper, given the positions before and after it and the move which was played to reach the one before
+pub fn extends(r#move: BitMove, previous_move: BitMove, before: &State, after: &State, pv_node: bool, singular: bool) -> bool {
+    // Checks, as long as they don't just give material away
+    let gives_check = after.is_in_check(after.to_move) && before.see(r#move) >= 0;
+
+    // Recapturing on the square where the opponent just captured, which otherwise tends to leave the exchange unfinished at the horizon
+    let recapture = pv_node && move_is_capture(r#move) && previous_move != 0 && move_is_capture(previous_move) && move_to(previous_move) == move_to(r#move);
+
+    // Pushing a passed pawn to the seventh rank, where it's about to promote
+    let to = move_to(r#move);
+    let seventh_rank = match before.to_move {
+        Colour::White => to / 8 == 6,
+        Colour::Black => to / 8 == 1
+    };
+    let passed_pawn_push = move_piece(r#move) == Piece::Pawn && seventh_rank && is_passed(after, to, before.to_move);
+
+    singular || gives_check || recapture || passed_pawn_push
+}
+
+// Whether a line which has already been extended by the given number of plies can be extended any further at this ply
+pub fn extension_allowed(path_extensions: usize, ply: usize, root_depth: usize) -> bool {
+    path_extensions < MAX_PATH_EXTENSIONS && ply < 2 * root_depth
+}
+
+// The TT holds mate scores relative to the position they're stored for rather than to the root, so that they stay correct when the same
+// position is reached at a different ply
+fn score_to_tt(score: isize, ply: usize) -> isize {
+    if score >= MATE_BOUND {
+        score + ply as isize
+    }
+    else if score <= -MATE_BOUND {
+        score - ply as isize
+    }
+    else {
+        score
+    }
+}
+
+fn score_from_tt(score: isize, ply: usize) -> isize {
+    if score >= MATE_BOUND {
+        score - ply as isize
+    }
+    else if score <= -MATE_BOUND {
+        score + ply as isize
+    }
+    else {
+        score
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper
+}
+
+pub struct SearchInfo {
+    pub depth: usize,
+    pub seldepth: usize,
+    pub multipv: usize,
+    pub score: isize,
+    pub bound: Bound,
+    pub nodes: usize,
+    pub duration: Duration,
+    pub hashfull: usize,
+    pub tt_hits: usize,
+    pub tb_hits: usize,
+    pub pv: Line
+}
+
+#[derive(Copy, Clone)]
+pub struct PvLine {
+    pub score: isize,
+    pub bound: Bound,
+    pub pv: Line
+}
 
 pub enum Message {
-    Info(usize, usize, usize, usize, Duration, BitMove, isize, Line), // depth, nodes, tt hits, tb_hits, duration, bestmove, eval, pv
+    Info(Box<SearchInfo>),
     Done,
-    Stop
+    Stop,
+    PonderHit
 }
 
 #[derive(Copy, Clone)]
@@ -49,117 +211,219 @@ impl fmt::Display for Line {
     }
 }
 
-struct TtEntry {
-    score: isize,
-    depth: usize,
-    flag: i8
+// How far the static eval has to be outside the window, per ply of depth remaining, for each kind of pruning to apply
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PruningMargins {
+    pub reverse_futility: isize,
+    pub razoring: isize,
+    pub futility_base: isize,
+    pub futility: isize
+}
+
+impl Default for PruningMargins {
+    fn default() -> Self {
+        Self {
+            reverse_futility: RFP_MARGIN,
+            razoring: RAZORING_MARGIN,
+            futility_base: FUTILITY_BASE_MARGIN,
+            futility: FUTILITY_MARGIN
+        }
+    }
 }
 
 pub struct Search {
     state: State,
-    depth: usize,
+    limits: SearchLimits,
     depth_searched: usize,
-    times: [Option<Duration>; 2],
     search_start: Instant,
-    search_duration: Option<Duration>,
+    time_manager: TimeManager,
+    move_overhead: Duration,
     search_active: bool,
+    pondering: bool,
     node_counter: usize,
+    seldepth: usize,
     best: (BitMove, isize),
     killers: [[BitMove; 2]; MAX_PLY],
-    history: [[[usize; MAX_PLY]; MAX_PLY]; 2],
-    tt_table: HashMap<u64, TtEntry>,
+    move_stack: [BitMove; MAX_PLY],
+    excluded_moves: [BitMove; MAX_PLY],
+    path_extensions: [usize; MAX_PLY + 1],
+    root_depth: usize,
+    nmp_min_ply: usize,
+    margins: PruningMargins,
+    move_history: MoveHistory,
+    move_histories: Arc<Mutex<Vec<MoveHistory>>>,
+    tt: Arc<TranspositionTable>,
     tt_hits: usize,
-    tablebase: Option<Tablebase<Chess>>,
+    network: Option<Arc<Network>>,
+    // The network's accumulators for the position at each ply of the current line
+    accumulators: [[Accumulator; 2]; MAX_PLY + 1],
+    tablebase: Option<Arc<Tablebase<Chess>>>,
     tb_hits: usize,
+    own_book: bool,
+    multi_pv: usize,
+    excluded_root_moves: Vec<BitMove>,
+    pv_lines: Vec<PvLine>,
     previous_pv: Line,
-    channels: Option<(Sender<Message>, Receiver<Message>)>
+    channels: Option<(Sender<Message>, Receiver<Message>)>,
+    thread_id: usize,
+    threads: usize,
+    stop: Arc<AtomicBool>,
+    nodes: Arc<AtomicUsize>,
+    helper_nodes: Vec<Arc<AtomicUsize>>
 }
 
 impl Search {
-    pub fn new(state: State, tb_directory: &Option<String>) -> Self {
-        let tablebase = match tb_directory {
-            Some(dir) => {
-                let mut tb = Tablebase::new();
-                tb.add_directory(dir).unwrap();
-                Some(tb)
-            },
-            None => None
-        };
-
+    pub fn new(state: State, tablebase: &Option<Arc<Tablebase<Chess>>>) -> Self {
         Self {
             state,
-            depth: usize::MAX,
+            limits: SearchLimits::new(),
             depth_searched: 0,
-            times: [None; 2],
             search_start: Instant::now(),
-            search_duration: None,
+            time_manager: TimeManager::unlimited(),
+            move_overhead: Duration::from_millis(0),
             search_active: false,
+            pondering: false,
             node_counter: 0,
+            seldepth: 0,
             best: (0, -MATE_VALUE),
             killers: [[0; 2]; MAX_PLY],
-            history: [[[0; MAX_PLY]; MAX_PLY]; 2],
-            tt_table: HashMap::new(),
+            move_stack: [0; MAX_PLY],
+            excluded_moves: [0; MAX_PLY],
+            path_extensions: [0; MAX_PLY + 1],
+            root_depth: 0,
+            nmp_min_ply: 0,
+            margins: PruningMargins::default(),
+            move_history: MoveHistory::default(),
+            move_histories: Arc::new(Mutex::new(Vec::new())),
+            tt: Arc::new(TranspositionTable::new(1)),
             tt_hits: 0,
-            tablebase: tablebase,
+            network: None,
+            accumulators: [[[0; HIDDEN_SIZE]; 2]; MAX_PLY + 1],
+            tablebase: tablebase.clone(),
             tb_hits: 0,
+            own_book: true,
+            multi_pv: 1,
+            excluded_root_moves: Vec::new(),
+            pv_lines: Vec::new(),
             previous_pv: Line::new(),
-            channels: None
+            channels: None,
+            thread_id: 0,
+            threads: 1,
+            stop: Arc::new(AtomicBool::new(false)),
+            nodes: Arc::new(AtomicUsize::new(0)),
+            helper_nodes: Vec::new()
         }
     }
 
-    pub fn set_depth(&mut self, depth: usize) {
-        self.depth = depth;
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+    pub fn set_move_overhead(&mut self, overhead: Duration) {
+        self.move_overhead = overhead;
+    }
+    pub fn set_own_book(&mut self, own_book: bool) {
+        self.own_book = own_book;
+    }
+    pub fn set_pruning_margins(&mut self, margins: PruningMargins) {
+        self.margins = margins;
+    }
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = max(multi_pv, 1);
     }
-    pub fn set_time(&mut self, colour: Colour, time: Option<Duration>) {
-        self.times[colour as usize] = time;
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = max(threads, 1);
     }
-    pub fn set_search_duration(&mut self, duration: Option<Duration>) {
-        self.search_duration = duration;
+    pub fn set_transposition_table(&mut self, tt: Arc<TranspositionTable>) {
+        self.tt = tt;
+    }
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network;
+    }
+    pub fn set_move_histories(&mut self, move_histories: Arc<Mutex<Vec<MoveHistory>>>) {
+        self.move_histories = move_histories;
     }
     pub fn set_channels(&mut self, channels: Option<(Sender<Message>, Receiver<Message>)>) {
         self.channels = channels;
     }
 
-    pub fn go(mut self) -> (BitMove, isize) {
-        if self.state.fullmove_number <= 6 {
+    pub fn go(&mut self) -> (BitMove, isize) {
+        // A book move would be sent straight away, but infinite and ponder searches have to hold on to their bestmove until told otherwise.
+        // Nor does the book know anything about searchmoves, so it's left out whenever the moves are restricted
+        if self.own_book && !self.limits.infinite && !self.limits.ponder && self.limits.searchmoves.is_empty() && self.state.fullmove_number <= 6 {
             if let Some(result) = BOOK.get(&self.state.hash) {
                 let mut rng = thread_rng();
-                self.best = (*rng.choose(&result).unwrap(), 0);
+                self.best = (*rng.choose(result).unwrap(), 0);
             }
         }
 
         if self.best.0 == 0 {
             self.search_start = Instant::now();
-            if self.search_duration.is_none() {
-                if let Some(duration) = self.times[self.state.to_move as usize] {
-                    self.search_duration = Some(duration / 20);
-                }
+            self.pondering = self.limits.ponder;
+            self.time_manager = if self.pondering { TimeManager::unlimited() } else { TimeManager::new(&self.limits, self.state.to_move, self.move_overhead) };
+            if self.root_moves() == 1 {
+                self.time_manager.forced_move();
             }
             self.search_active = true;
-            
-            for depth in 1..=self.depth {
-                let mut pv = Line::new();
-                self.negamax(-MATE_VALUE, MATE_VALUE, depth, 0, &mut pv, true);
-                self.previous_pv = pv;
-    
-                if !self.search_active && depth > 1 {
-                    break;
-                }
-    
-                self.depth_searched = depth;
-    
-                if let Some(channels) = &mut self.channels {
-                    channels.0.send(Message::Info(depth, self.node_counter, self.tt_hits, self.tb_hits, Instant::now().duration_since(self.search_start), self.best.0, self.best.1, self.previous_pv)).unwrap();
+
+            // Each thread carries on with its own history tables from the last search, or starts new ones if there are more threads this time
+            let mut histories = std::mem::take(&mut *self.move_histories.lock().unwrap());
+            histories.resize_with(self.threads, MoveHistory::new);
+            for history in &mut histories {
+                history.age();
+            }
+            let mut histories = histories.into_iter();
+            self.move_history = histories.next().unwrap();
+
+            // Helpers share the TT with us, which is how they speed up the main search, and are told to finish through the stop flag
+            let helpers: Vec<_> = (1..self.threads).zip(histories).map(|(thread_id, history)| {
+                let helper = self.helper(thread_id, history);
+                self.helper_nodes.push(helper.nodes.clone());
+                thread::spawn(move || helper.run_helper())
+            }).collect();
+
+            self.iterate();
+
+            // In infinite mode or while pondering the bestmove must not be sent until the GUI asks for it, even if we've run out of things to search
+            if (self.limits.infinite || self.pondering) && self.search_active {
+                if let Some(channels) = &self.channels {
+                    while let Ok(message) = channels.1.recv() {
+                        match message {
+                            Message::Stop => break,
+                            Message::PonderHit if !self.limits.infinite => break,
+                            _ => {}
+                        }
+                    }
                 }
-    
-                // Slightly hacky way of detecting if we've found mate, because then we don't need to search at any higher depths
-                if self.best.1 >= MATE_VALUE - 100 {
-                    break;
+            }
+
+            self.stop.store(true, Ordering::Relaxed);
+            let mut histories = vec![std::mem::take(&mut self.move_history)];
+            for helper in helpers {
+                let (depth, line, history) = helper.join().unwrap();
+                histories.push(history);
+                // A helper which got further than we did has the more reliable move, unless we're reporting several lines
+                if let Some(line) = line {
+                    if self.multi_pv == 1 && depth > self.depth_searched {
+                        self.best = (line.pv.moves[0], line.score);
+                        self.pv_lines = vec![line];
+                        self.depth_searched = depth;
+                    }
                 }
             }
+            *self.move_histories.lock().unwrap() = histories;
         }
-        
-        if let Some(channels) = self.channels {
+        else {
+            let mut pv = Line::new();
+            pv.moves[0] = self.best.0;
+            pv.length = 1;
+            self.pv_lines = vec![PvLine {
+                score: self.best.1,
+                bound: Bound::Exact,
+                pv
+            }];
+        }
+
+        if let Some(channels) = &self.channels {
             channels.0.send(Message::Done).unwrap();
         }
         (self.best.0, match self.state.to_move {
@@ -168,142 +432,523 @@ impl Search {
         })
     }
 
-    fn negamax(&mut self, mut alpha: isize, mut beta: isize, mut depth: usize, current_ply: usize, mut pline: &mut Line, mut in_pv: bool) -> isize {
-        if self.depth_searched > 1 && self.node_counter % 2048 == 0 {
-            if let Some(duration) = self.search_duration {
-                if Instant::now().duration_since(self.search_start) > duration {
-                    self.search_active = false;
-                }
+    fn iterate(&mut self) {
+        if let Some(network) = &self.network {
+            self.accumulators[0] = network.accumulators(&self.state);
+        }
+
+        let max_depth = min(self.limits.depth.unwrap_or(MAX_PLY - 1), MAX_PLY - 1);
+        for depth in 1..=max_depth {
+            // Every helper still searches the final depth, so that none of them finishes early and sits idle
+            if self.thread_id > 0 && depth < max_depth && (depth + SKIP_PHASE[(self.thread_id - 1) % 20]) / SKIP_SIZE[(self.thread_id - 1) % 20] % 2 == 1 {
+                continue;
             }
 
-            if let Some(channels) = &self.channels {
-                match channels.1.try_recv() {
-                    Ok(Message::Stop) => {
-                        self.search_active = false;
+            let mut lines: Vec<PvLine> = Vec::new();
+            self.seldepth = 0;
+            self.root_depth = depth;
+
+            // Each additional line is found by searching the root again with all of the previous lines' moves excluded
+            for multipv in 0..self.multi_pv {
+                self.excluded_root_moves = lines.iter().map(|line| line.pv.moves[0]).collect();
+                self.previous_pv = self.pv_lines.get(multipv).map_or(Line::new(), |line| line.pv);
+
+                let mut delta = ASPIRATION_WINDOW;
+                let (mut alpha, mut beta) = match self.pv_lines.get(multipv) {
+                    Some(line) if depth >= ASPIRATION_MIN_DEPTH && mate_in(line.score).is_none() => (line.score - delta, line.score + delta),
+                    _ => (-INFINITY, INFINITY)
+                };
+                let (mut pv, mut score);
+                loop {
+                    self.best = (0, -INFINITY);
+                    pv = Line::new();
+                    score = self.negamax(alpha, beta, depth, 0, &mut pv, true);
+
+                    if !self.search_active {
+                        break;
+                    }
+
+                    // Widen the window on whichever side the score fell outside of, by more each time
+                    if score <= alpha && alpha > -INFINITY {
+                        self.send_info(depth, multipv + 1, &PvLine {
+                            score: alpha,
+                            bound: Bound::Upper,
+                            pv: self.previous_pv
+                        });
+                        beta = (alpha + beta) / 2;
+                        alpha = max(alpha - delta, -INFINITY);
+                    }
+                    else if score >= beta && beta < INFINITY {
+                        let mut pv = Line::new();
+                        pv.moves[0] = self.best.0;
+                        pv.length = 1;
+                        self.send_info(depth, multipv + 1, &PvLine {
+                            score: beta,
+                            bound: Bound::Lower,
+                            pv
+                        });
+                        beta = min(beta + delta, INFINITY);
+                    }
+                    else {
+                        break;
+                    }
+                    delta *= 2;
+                }
+
+                if multipv == 0 && self.best.0 == 0 {
+                    // Checkmate or stalemate, so there's nothing to search
+                    self.best.1 = score;
+                }
+                if self.best.0 == 0 || (!self.search_active && depth > 1) {
+                    break;
+                }
+
+                if pv.length == 0 {
+                    pv.moves[0] = self.best.0;
+                    pv.length = 1;
+                }
+                lines.push(PvLine {
+                    score: self.best.1,
+                    bound: if self.best.1 <= alpha {
+                        Bound::Upper
+                    } else if self.best.1 >= beta {
+                        Bound::Lower
+                    } else {
+                        Bound::Exact
                     },
-                    _ => {}
+                    pv
+                });
+
+                if !self.search_active {
+                    break;
                 }
             }
 
-            if !self.search_active {
+            if !self.search_active && depth > 1 {
+                if let Some(line) = self.pv_lines.first() {
+                    self.best = (line.pv.moves[0], line.score);
+                    self.previous_pv = line.pv;
+                }
+                break;
+            }
+
+            if lines.is_empty() {
+                break;
+            }
+
+            // A later line can come out ahead of an earlier one if the search is unstable, but the GUI expects them in order
+            lines.sort_by_key(|line| Reverse(line.score));
+            self.best = (lines[0].pv.moves[0], lines[0].score);
+            self.previous_pv = lines[0].pv;
+            self.pv_lines = lines;
+            self.depth_searched = depth;
+
+            for (i, line) in self.pv_lines.iter().enumerate() {
+                self.send_info(depth, i + 1, line);
+            }
+
+            if let Some(mate) = self.limits.mate {
+                if matches!(mate_in(self.best.1), Some(n) if n > 0 && n as usize <= mate) || depth >= 2 * mate {
+                    break;
+                }
+            }
+
+            if self.thread_id == 0 {
+                self.time_manager.update(self.best.0, self.best.1);
+                if self.time_manager.soft_limit_reached() {
+                    break;
+                }
+            }
+
+            // Once a mate has been searched to its full length, searching any deeper won't turn up a different result
+            if !self.limits.infinite && !self.pondering && matches!(mate_in(self.best.1), Some(n) if depth >= 2 * n.unsigned_abs()) {
+                break;
+            }
+        }
+    }
+
+    fn send_info(&self, depth: usize, multipv: usize, line: &PvLine) {
+        if let Some(channels) = &self.channels {
+            channels.0.send(Message::Info(Box::new(SearchInfo {
+                depth,
+                seldepth: self.seldepth,
+                multipv,
+                score: line.score,
+                bound: line.bound,
+                nodes: self.total_nodes(),
+                duration: Instant::now().duration_since(self.search_start),
+                hashfull: self.tt.hashfull(),
+                tt_hits: self.tt_hits,
+                tb_hits: self.tb_hits,
+                pv: line.pv
+            }))).unwrap();
+        }
+    }
+
+    fn helper(&self, thread_id: usize, move_history: MoveHistory) -> Self {
+        let mut helper = Self::new(self.state, &self.tablebase);
+        helper.thread_id = thread_id;
+        helper.move_history = move_history;
+        helper.limits = self.limits.clone();
+        helper.margins = self.margins;
+        helper.tt = self.tt.clone();
+        helper.network = self.network.clone();
+        helper.stop = self.stop.clone();
+        helper
+    }
+
+    fn run_helper(mut self) -> (usize, Option<PvLine>, MoveHistory) {
+        self.search_start = Instant::now();
+        self.search_active = true;
+        self.iterate();
+        self.nodes.store(self.node_counter, Ordering::Relaxed);
+
+        (self.depth_searched, self.pv_lines.first().copied(), self.move_history)
+    }
+
+    fn total_nodes(&self) -> usize {
+        self.node_counter + self.helper_nodes.iter().map(|nodes| nodes.load(Ordering::Relaxed)).sum::<usize>()
+    }
+
+    pub fn pv_lines(&self) -> &[PvLine] {
+        &self.pv_lines
+    }
+
+    // The opponent played the move we were pondering on, so from now on we're searching on our own clock
+    fn ponderhit(&mut self) {
+        if self.pondering {
+            self.pondering = false;
+            self.search_start = Instant::now();
+            self.time_manager = TimeManager::new(&self.limits, self.state.to_move, self.move_overhead);
+            if self.root_moves() == 1 {
+                self.time_manager.forced_move();
+            }
+        }
+    }
+
+    fn root_moves(&self) -> usize {
+        generate_moves(&self.state).filter(|r#move| {
+            let mut copy = self.state;
+            (self.limits.searchmoves.is_empty() || self.limits.searchmoves.contains(r#move)) && copy.make_move(*r#move).is_ok()
+        }).count()
+    }
+
+    // Whether a root move is one searchmoves asks for and which hasn't already been given a MultiPV line of its own
+    fn root_move_allowed(&self, r#move: BitMove) -> bool {
+        !self.excluded_root_moves.contains(&r#move) && (self.limits.searchmoves.is_empty() || self.limits.searchmoves.contains(&r#move))
+    }
+
+    fn root_restricted(&self) -> bool {
+        !self.excluded_root_moves.is_empty() || !self.limits.searchmoves.is_empty()
+    }
+
+    // Polls for anything which should stop the search. This is called every time the node counter goes up, but only does anything every so often
+    fn check_limits(&mut self) {
+        if !self.node_counter.is_multiple_of(1024) {
+            return;
+        }
+
+        self.nodes.store(self.node_counter, Ordering::Relaxed);
+        // Nothing is allowed to stop the search until there's a move to play
+        if self.depth_searched == 0 {
+            return;
+        }
+
+        if self.stop.load(Ordering::Relaxed) {
+            self.search_active = false;
+        }
+
+        match self.channels.as_ref().and_then(|channels| channels.1.try_recv().ok()) {
+            Some(Message::Stop) => {
+                self.search_active = false;
+            },
+            Some(Message::PonderHit) => {
+                self.ponderhit();
+            },
+            _ => {}
+        }
+
+        if self.time_manager.hard_limit_reached() {
+            self.search_active = false;
+        }
+    }
+
+    fn negamax(&mut self, mut alpha: isize, mut beta: isize, mut depth: usize, current_ply: usize, pline: &mut Line, mut in_pv: bool) -> isize {
+        // Whatever we return early with, the line from any previous search at this ply mustn't be left behind
+        pline.length = 0;
+
+        // The limit counts every thread's nodes, so it's up to the main thread to check it and the helpers are stopped along with it
+        if let Some(nodes) = self.limits.nodes {
+            if self.thread_id == 0 && self.depth_searched > 0 && self.total_nodes() >= nodes {
+                self.search_active = false;
                 return alpha;
             }
         }
         
         if current_ply >= MAX_PLY {
-            return relative_eval(&self.state);
+            return self.evaluate(current_ply);
+        }
+
+        self.seldepth = max(self.seldepth, current_ply + 1);
+
+        if current_ply > 0 && self.state.is_draw(current_ply) {
+            return 0;
         }
 
-        if self.state.is_in_check(self.state.to_move) {
-            depth += 1;
+        // Mate distance pruning: there's no point looking for a mate here if a shorter one has already been found elsewhere
+        if current_ply > 0 {
+            alpha = max(alpha, -MATE_VALUE + current_ply as isize);
+            beta = min(beta, MATE_VALUE - current_ply as isize - 1);
+            if alpha >= beta {
+                return alpha;
+            }
         }
 
+        let pv_node = beta - alpha > 1;
+        let in_check = self.state.is_in_check(self.state.to_move);
+        // Set when this node is being searched without one of its moves, to see whether that move is singular
+        let excluded_move = self.excluded_moves[current_ply];
+
         let original_alpha = alpha;
 
-        if let Some(tt_entry) = self.tt_table.get(&self.state.hash) {
-            if tt_entry.depth >= depth {
+        let mut tt_move = 0;
+
+        // The root is never cut off by the TT, since its entry doesn't account for moves excluded by MultiPV or searchmoves.
+        // Likewise, a search with an excluded move mustn't be cut off by the entry for the full position
+        let tt_entry = self.tt.probe(self.state.hash);
+        if let Some(tt_entry) = tt_entry {
+            tt_move = tt_entry.r#move;
+
+            if current_ply > 0 && excluded_move == 0 && tt_entry.depth >= depth {
                 self.tt_hits += 1;
+                let tt_score = score_from_tt(tt_entry.score, current_ply);
 
-                if tt_entry.flag == 0 {
-                    return tt_entry.score;
-                }
-                if tt_entry.flag == -1 {
-                    alpha = max(alpha, tt_entry.score);
-                }
-                if tt_entry.flag == 1 {
-                    beta = min(beta, tt_entry.score);
+                match tt_entry.bound {
+                    Bound::Exact => {
+                        return tt_score;
+                    },
+                    Bound::Lower => {
+                        alpha = max(alpha, tt_score);
+                    },
+                    Bound::Upper => {
+                        beta = min(beta, tt_score);
+                    }
                 }
 
                 if alpha >= beta {
-                    return tt_entry.score;
-                }
-            }
-        }
-
-        if let Some(tablebase) = &self.tablebase {
-            if count_bits(self.state.occupancy) <= 5 {
-                let pos: Chess = self.state.to_fen().parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
-                let tb_result = tablebase.best_move(&pos).unwrap();
-    
-                let (r#move, score) = match tb_result {
-                    Some(tb_result) => (
-                        encode_move(
-                            tb_result.0.from().unwrap() as usize,
-                            tb_result.0.to() as usize,
-                            Piece::Pawn,
-                            match tb_result.0.promotion() {
-                                Some(p) => Some(match p {
-                                    Role::Pawn => Piece::Pawn,
-                                    Role::Knight => Piece::Knight,
-                                    Role::Bishop => Piece::Bishop,
-                                    Role::Rook => Piece::Rook,
-                                    Role::Queen => Piece::Queen,
-                                    Role::King => Piece::King
-                                }),
-                                None => None
-                            },
-                            false,
-                            false,
-                            false,
-                            false
-                        ),
-                        match tb_result.1 {
-                            Dtz(x) if x > 0 => -MATE_VALUE + current_ply as isize + x as isize,
-                            Dtz(x) if x < 0 => MATE_VALUE - current_ply as isize + x as isize,
-                            _ => 0
-                        }
-                    ),
-                    None => if self.state.is_in_check(self.state.to_move) { (0, -MATE_VALUE + current_ply as isize) } else { (0, 0) }
-                };
-
-                self.tb_hits += 1;
-    
-                self.tt_table.insert(self.state.hash, TtEntry {
-                    score,
-                    depth: usize::MAX,
-                    flag: 0
-                });
-    
-                if current_ply == 0 {
-                    self.best = (r#move, score);
-                    self.search_active = false;
+                    return tt_score;
                 }
-    
-                return score;
             }
         }
 
-        if depth == 0 {
-            pline.length = 0;
+        // The tablebase's move at the root may be one which searchmoves or an earlier MultiPV line has left out, so then the root is searched as usual
+        let tb_result = if current_ply == 0 && self.root_restricted() { None } else { self.probe_tablebase(current_ply) };
+        if let Some((r#move, score)) = tb_result {
+            self.tt.store(self.state.hash, score_to_tt(score, current_ply), usize::MAX, Bound::Exact, r#move);
 
-            return self.quiescence(alpha, beta, current_ply);
+            if current_ply == 0 {
+                self.best = (r#move, score);
+                self.search_active = false;
+            }
+
+            return score;
+        }
+
+        // Internal iterative reductions. Never at the root, where the iteration has to search the full depth it reports
+        if pv_node && current_ply > 0 && tt_move == 0 && excluded_move == 0 && depth >= IIR_MIN_DEPTH {
+            depth -= 1;
+        }
+
+        if depth == 0 {
+            return self.quiescence(alpha, beta, current_ply, true);
         }
 
         self.node_counter += 1;
+        self.check_limits();
+        if !self.search_active {
+            return alpha;
+        }
 
-        if current_ply > 0 && current_ply % 2 == 0 && self.state.is_repetition() {
-            return 0;
+        // None of the static eval based pruning can be trusted in check, or when mate scores are involved, and nothing else uses the static eval
+        let can_prune = !pv_node && !in_check && current_ply > 0 && excluded_move == 0 && alpha.abs() < MATE_BOUND && beta.abs() < MATE_BOUND;
+        let static_eval = if can_prune { self.evaluate(current_ply) } else { 0 };
+
+        // Reverse futility pruning: we're so far above beta that the opponent is very unlikely to be able to do anything about it
+        if can_prune && depth <= RFP_MAX_DEPTH && static_eval - self.margins.reverse_futility * depth as isize >= beta {
+            return beta;
+        }
+
+        // Razoring: we're so far below alpha that only captures could plausibly save us, so check with a quiescence search
+        if can_prune && depth <= RAZORING_MAX_DEPTH && static_eval + self.margins.razoring * depth as isize <= alpha {
+            let score = self.quiescence(alpha, beta, current_ply, true);
+            if depth == 1 || score <= alpha {
+                return score;
+            }
+        }
+
+        // If we're still above beta after handing the opponent a free move then a real move will almost certainly be too, so we can cut off early.
+        // This goes wrong in zugzwang, which is why it's skipped when we only have pawns left and verified by a normal search when deep
+        let non_pawn_material = self.state.colours[self.state.to_move as usize] & !(self.state.pieces[Piece::Pawn as usize] | self.state.pieces[Piece::King as usize]);
+        if can_prune && depth >= NMP_MIN_DEPTH && current_ply >= self.nmp_min_ply && self.move_stack[current_ply - 1] != 0 && non_pawn_material != 0 && static_eval >= beta {
+            let reduction = min(3 + depth / 6 + min((static_eval - beta) as usize / NMP_EVAL_DIVISOR, NMP_MAX_EVAL_REDUCTION), depth);
+
+            let copy = self.state;
+            self.state.make_null_move();
+            self.update_accumulators(current_ply, &copy);
+            self.move_stack[current_ply] = 0;
+            let score = -self.negamax(-beta, -beta + 1, depth - reduction, current_ply + 1, &mut Line::new(), false);
+            self.state = copy;
+
+            if !self.search_active {
+                return alpha;
+            }
+
+            if score >= beta {
+                // A mate found after passing can't be trusted, since passing isn't actually legal
+                let score = if score >= MATE_BOUND { beta } else { score };
+
+                if depth < NMP_VERIFICATION_DEPTH {
+                    return score;
+                }
+
+                // Null moves are disabled for the first part of the verification search, so that it can't just cut off the same way again
+                let nmp_min_ply = self.nmp_min_ply;
+                self.nmp_min_ply = current_ply + 3 * (depth - reduction) / 4;
+                let verification_score = self.negamax(beta - 1, beta, depth - reduction, current_ply, &mut Line::new(), false);
+                self.nmp_min_ply = nmp_min_ply;
+
+                if verification_score >= beta {
+                    return score;
+                }
+            }
+        }
+
+        // ProbCut: look for a good capture which beats beta by a margin in a quiescence search and then a reduced search, and cut off if there is one.
+        // Skipped if the TT already tells us that the reduced search would fail
+        let probcut_beta = beta + PROBCUT_MARGIN;
+        let tt_refutes_probcut = matches!(tt_entry, Some(entry) if entry.depth + PROBCUT_REDUCTION > depth && score_from_tt(entry.score, current_ply) < probcut_beta);
+        if can_prune && depth >= PROBCUT_MIN_DEPTH && !tt_refutes_probcut {
+            let mut captures = generate_moves(&self.state);
+            self.sort_moves(&mut captures, current_ply, false, tt_move);
+            for r#move in captures {
+                if !move_is_capture(r#move) || self.state.see(r#move) < probcut_beta - static_eval {
+                    continue;
+                }
+
+                let copy = self.state;
+                if self.state.make_move(r#move).is_err() {
+                    continue;
+                }
+                self.update_accumulators(current_ply, &copy);
+                self.move_stack[current_ply] = r#move;
+                let mut score = -self.quiescence(-probcut_beta, -probcut_beta + 1, current_ply + 1, false);
+                if score >= probcut_beta {
+                    score = -self.negamax(-probcut_beta, -probcut_beta + 1, depth - PROBCUT_REDUCTION, current_ply + 1, &mut Line::new(), false);
+                }
+                self.state = copy;
+
+                if !self.search_active {
+                    return alpha;
+                }
+
+                if score >= probcut_beta {
+                    self.tt.store(self.state.hash, score_to_tt(score, current_ply), depth - PROBCUT_REDUCTION + 1, Bound::Lower, r#move);
+                    return score;
+                }
+            }
         }
 
         let mut line = Line::new();
 
         let mut moves = generate_moves(&self.state);
-        self.sort_moves(&mut moves, current_ply, in_pv);
+
+        self.sort_moves(&mut moves, current_ply, in_pv, tt_move);
         let mut num_legal_moves = 0;
         let mut bestmove: BitMove = 0;
-        while let Some(r#move) = moves.next() {
-            let copy = self.state.clone();
+        let previous_moves = self.previous_moves(current_ply);
+        // Moves which were searched without causing a cutoff, to be penalised in the history if a later one does
+        let (mut quiets_tried, mut num_quiets_tried) = ([0; 64], 0);
+        let (mut captures_tried, mut num_captures_tried) = ([(0, Piece::Pawn); 32], 0);
+        for r#move in moves {
+            if current_ply == 0 && !self.root_move_allowed(r#move) {
+                continue;
+            }
+            if r#move == excluded_move {
+                continue;
+            }
+
+            let is_quiet = !move_is_capture(r#move) && move_promotion_piece(r#move).is_none();
+            let history_score = if is_quiet { self.move_history.quiet_score(self.state.to_move, r#move, previous_moves) } else { 0 };
+            if is_quiet && !pv_node && !in_check && depth <= LMP_MAX_DEPTH && num_legal_moves >= 3 + depth * depth && alpha > -MATE_BOUND {
+                continue;
+            }
+            // Futility pruning: a quiet move is very unlikely to make up the difference between the static eval and alpha
+            if is_quiet && can_prune && depth <= FUTILITY_MAX_DEPTH && num_legal_moves > 0 && static_eval + self.margins.futility_base + self.margins.futility * depth as isize <= alpha {
+                continue;
+            }
+            if is_quiet && !pv_node && !in_check && depth <= SEE_QUIET_MAX_DEPTH && num_legal_moves > 0 && self.state.see(r#move) < -SEE_QUIET_MARGIN * depth as isize {
+                continue;
+            }
+
+            // Singular extensions: if the TT move is much better than every alternative, it's worth searching more deeply.
+            // If even the alternatives beat beta, then more than one move does and we can expect a cutoff without searching any further
+            let mut singular = false;
+            if let Some(tt_entry) = tt_entry.filter(|entry| r#move == tt_move && current_ply > 0 && excluded_move == 0 && depth >= SINGULAR_MIN_DEPTH && entry.depth + 3 >= depth && entry.bound != Bound::Upper) {
+                let tt_score = score_from_tt(tt_entry.score, current_ply);
+                if tt_score.abs() < MATE_BOUND {
+                    let singular_beta = tt_score - SINGULAR_MARGIN * depth as isize;
+                    self.excluded_moves[current_ply] = r#move;
+                    let score = self.negamax(singular_beta - 1, singular_beta, (depth - 1) / 2, current_ply, &mut Line::new(), false);
+                    self.excluded_moves[current_ply] = 0;
+
+                    if score < singular_beta {
+                        singular = true;
+                    }
+                    else if singular_beta >= beta {
+                        return singular_beta;
+                    }
+                }
+            }
+
+            let copy = self.state;
             if self.state.make_move(r#move).is_err() {
                 continue;
             }
             num_legal_moves += 1;
+            self.update_accumulators(current_ply, &copy);
+            self.move_stack[current_ply] = r#move;
+
+            let extension = self.extension(r#move, current_ply, pv_node, singular, &copy);
+            self.path_extensions[current_ply + 1] = self.path_extensions[current_ply] + extension;
+            let new_depth = depth + extension;
+
             let score = if num_legal_moves == 1 {
-                -self.negamax(-beta, -alpha, depth-1, current_ply+1, &mut line, in_pv)
+                -self.negamax(-beta, -alpha, new_depth-1, current_ply+1, &mut line, in_pv)
             }
             else {
-                let null_window_score = -self.negamax(-alpha-1, -alpha, depth-1, current_ply+1, &mut line, in_pv);
+                let mut reduction = 0;
+                if is_quiet && !in_check && extension == 0 && depth >= LMR_MIN_DEPTH {
+                    reduction = LMR_TABLE[min(depth, 63)][min(num_legal_moves, 63)] as isize;
+                    if pv_node {
+                        reduction -= 1;
+                    }
+                    if self.killers[current_ply].contains(&r#move) {
+                        reduction -= 1;
+                    }
+                    if self.state.is_in_check(self.state.to_move) {
+                        reduction -= 1;
+                    }
+                    reduction -= (history_score / LMR_HISTORY_DIVISOR).clamp(-2, 2);
+                    // Always leave at least one ply to search
+                    reduction = reduction.clamp(0, new_depth as isize - 2);
+                }
+
+                let mut null_window_score = -self.negamax(-alpha-1, -alpha, new_depth-1 - reduction as usize, current_ply+1, &mut line, in_pv);
+                if reduction > 0 && null_window_score > alpha {
+                    null_window_score = -self.negamax(-alpha-1, -alpha, new_depth-1, current_ply+1, &mut line, in_pv);
+                }
                 if alpha < null_window_score && null_window_score < beta {
-                    -self.negamax(-beta, -null_window_score, depth-1, current_ply+1, &mut line, in_pv)
+                    -self.negamax(-beta, -null_window_score, new_depth-1, current_ply+1, &mut line, in_pv)
                 }
                 else {
                     null_window_score
@@ -312,24 +957,39 @@ impl Search {
             self.state = copy;
             in_pv = false;
             if score >= beta {
-                if !move_is_capture(r#move) {
-                    self.killers[current_ply][1] = self.killers[current_ply][0];
-                    self.killers[current_ply][0] = r#move;
+                if is_quiet {
+                    if self.killers[current_ply][0] != r#move {
+                        self.killers[current_ply][1] = self.killers[current_ply][0];
+                        self.killers[current_ply][0] = r#move;
+                    }
+                    self.move_history.update_quiets(self.state.to_move, r#move, &quiets_tried[..num_quiets_tried], previous_moves, depth);
+                }
+                else if move_is_capture(r#move) {
+                    self.move_history.update_captures(self.state.to_move, (r#move, self.captured_piece(r#move)), &captures_tried[..num_captures_tried], depth);
                 }
 
-                self.tt_table.insert(self.state.hash, TtEntry {
-                    score: beta,
-                    depth,
-                    flag: -1
-                });
+                // Results from an aborted search are unreliable, so mustn't be allowed to outlive it in the TT
+                if self.search_active && excluded_move == 0 {
+                    self.tt.store(self.state.hash, score_to_tt(beta, current_ply), depth, Bound::Lower, r#move);
+
+                    // Lets a fail high at the root report which move caused it
+                    if current_ply == 0 {
+                        self.best = (r#move, beta);
+                    }
+                }
 
                 return beta;
             }
-            if score > alpha {
-                if !move_is_capture(r#move) {
-                    self.history[self.state.to_move as usize][move_from(r#move)][move_to(r#move)] += depth;
-                }
+            if is_quiet && num_quiets_tried < quiets_tried.len() {
+                quiets_tried[num_quiets_tried] = r#move;
+                num_quiets_tried += 1;
+            }
+            else if move_is_capture(r#move) && num_captures_tried < captures_tried.len() {
+                captures_tried[num_captures_tried] = (r#move, self.captured_piece(r#move));
+                num_captures_tried += 1;
+            }
 
+            if score > alpha {
                 pline.moves[0] = r#move;
                 for i in 0..line.length {
                     pline.moves[i+1] = line.moves[i];
@@ -343,6 +1003,18 @@ impl Search {
         }
 
         if num_legal_moves == 0 {
+            // The only legal move is the excluded one, which makes it singular whatever it scores
+            if excluded_move != 0 {
+                return alpha;
+            }
+            // Every root move may have been left out by searchmoves or earlier MultiPV lines, which says nothing about the position itself
+            if current_ply == 0 && self.root_restricted() && generate_moves(&self.state).any(|r#move| {
+                let mut copy = self.state;
+                copy.make_move(r#move).is_ok()
+            }) {
+                return alpha;
+            }
+
             if self.state.is_in_check(self.state.to_move) {
                 alpha = -MATE_VALUE + (current_ply as isize);
             }
@@ -355,90 +1027,74 @@ impl Search {
             self.best = (bestmove, alpha);
         }
 
-        self.tt_table.insert(self.state.hash, TtEntry {
-            score: alpha,
-            depth,
-            flag: if alpha <= original_alpha {
-                1
+        if self.search_active && excluded_move == 0 {
+            self.tt.store(self.state.hash, score_to_tt(alpha, current_ply), depth, if alpha <= original_alpha {
+                Bound::Upper
             } else if alpha >= beta {
-                -1
+                Bound::Lower
             } else {
-                0
-            }
-        });
+                Bound::Exact
+            }, bestmove);
+        }
 
         alpha
     }
 
-    fn quiescence(&mut self, mut alpha: isize, beta: isize, current_ply: usize) -> isize {
-        if let Some(duration) = self.search_duration {
-            if self.depth_searched > 1 && self.node_counter % 2048 == 0 && Instant::now().duration_since(self.search_start) > duration {
-                self.search_active = false;
-
-                return alpha;
-            }
+    // Searches captures, promotions and, straight after the main search, checks until the position is quiet. When in check every evasion is searched instead
+    fn quiescence(&mut self, mut alpha: isize, beta: isize, current_ply: usize, quiet_checks: bool) -> isize {
+        self.node_counter += 1;
+        self.check_limits();
+        if !self.search_active {
+            return alpha;
         }
 
-        self.node_counter += 1;
+        if current_ply > 0 && self.state.is_draw(current_ply) {
+            return 0;
+        }
 
         if current_ply >= MAX_PLY {
-            return relative_eval(&self.state);
-        }
-
-        if let Some(tablebase) = &self.tablebase {
-            if count_bits(self.state.occupancy) <= 5 {
-                let pos: Chess = self.state.to_fen().parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
-                let tb_result = tablebase.best_move(&pos).unwrap();
-    
-                let (r#move, score) = match tb_result {
-                    Some(tb_result) => (
-                        encode_move(
-                            tb_result.0.from().unwrap() as usize,
-                            tb_result.0.to() as usize,
-                            Piece::Pawn,
-                            match tb_result.0.promotion() {
-                                Some(p) => Some(match p {
-                                    Role::Pawn => Piece::Pawn,
-                                    Role::Knight => Piece::Knight,
-                                    Role::Bishop => Piece::Bishop,
-                                    Role::Rook => Piece::Rook,
-                                    Role::Queen => Piece::Queen,
-                                    Role::King => Piece::King
-                                }),
-                                None => None
-                            },
-                            false,
-                            false,
-                            false,
-                            false
-                        ),
-                        match tb_result.1 {
-                            Dtz(x) if x > 0 => -MATE_VALUE + current_ply as isize + x as isize,
-                            Dtz(x) if x < 0 => MATE_VALUE - current_ply as isize + x as isize,
-                            _ => 0
-                        }
-                    ),
-                    None => if self.state.is_in_check(self.state.to_move) { (0, -MATE_VALUE + current_ply as isize) } else { (0, 0) }
-                };
+            return self.evaluate(current_ply);
+        }
 
-                self.tb_hits += 1;
-    
-                self.tt_table.insert(self.state.hash, TtEntry {
-                    score,
-                    depth: usize::MAX,
-                    flag: 0
-                });
-    
-                if current_ply == 0 {
-                    self.best = (r#move, score);
-                    self.search_active = false;
+        self.seldepth = max(self.seldepth, current_ply + 1);
+
+        let original_alpha = alpha;
+
+        // Every quiescence result is stored with depth 0, so any entry is deep enough to use
+        let mut tt_move = 0;
+        if let Some(tt_entry) = self.tt.probe(self.state.hash) {
+            tt_move = tt_entry.r#move;
+
+            if current_ply > 0 {
+                let tt_score = score_from_tt(tt_entry.score, current_ply);
+                let cutoff = match tt_entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => tt_score >= beta,
+                    Bound::Upper => tt_score <= alpha
+                };
+                if cutoff {
+                    self.tt_hits += 1;
+                    return tt_score;
                 }
-    
-                return score;
             }
         }
 
-        let standing_pat = relative_eval(&self.state);
+        // The tablebase's move at the root may be one which searchmoves or an earlier MultiPV line has left out, so then the root is searched as usual
+        let tb_result = if current_ply == 0 && self.root_restricted() { None } else { self.probe_tablebase(current_ply) };
+        if let Some((r#move, score)) = tb_result {
+            self.tt.store(self.state.hash, score_to_tt(score, current_ply), usize::MAX, Bound::Exact, r#move);
+
+            if current_ply == 0 {
+                self.best = (r#move, score);
+                self.search_active = false;
+            }
+
+            return score;
+        }
+
+        // In check there's no standing pat, since we might have no way out
+        let in_check = self.state.is_in_check(self.state.to_move);
+        let standing_pat = if in_check { -MATE_VALUE + current_ply as isize } else { self.evaluate(current_ply) };
         if standing_pat >= beta {
             return beta;
         }
@@ -446,59 +1102,178 @@ impl Search {
             alpha = standing_pat;
         }
 
+        let mut best_move = 0;
+        let mut num_legal_moves = 0;
         let mut moves = generate_moves(&self.state);
-        self.sort_moves(&mut moves, current_ply, false);
-        while let Some(r#move) = moves.next() {
-            if !move_is_capture(r#move) {
-                continue;
+        self.sort_moves(&mut moves, current_ply, false, tt_move);
+        for r#move in moves {
+            let promotion_piece = move_promotion_piece(r#move);
+            let is_quiet = !move_is_capture(r#move) && promotion_piece != Some(Piece::Queen);
+            if !in_check {
+                // Underpromotions are almost never better than promoting to a queen, and only lengthen the search here
+                if promotion_piece.is_some_and(|piece| piece != Piece::Queen) {
+                    continue;
+                }
+                // Captures which lose material can't be what stops the position from being quiet, and nor can checks which hang a piece
+                if (is_quiet && !quiet_checks) || self.state.see(r#move) < 0 {
+                    continue;
+                }
+                // Delta pruning: skip captures which couldn't bring us back up to alpha even with a margin for the positional gain
+                if !is_quiet && alpha.abs() < MATE_BOUND {
+                    let gain = if move_is_ep(r#move) { SEE_VALUES[Piece::Pawn as usize] } else if move_is_capture(r#move) { SEE_VALUES[self.captured_piece(r#move) as usize] } else { 0 }
+                        + promotion_piece.map_or(0, |piece| SEE_VALUES[piece as usize] - SEE_VALUES[Piece::Pawn as usize]);
+                    if standing_pat + gain + DELTA_MARGIN <= alpha {
+                        continue;
+                    }
+                }
             }
-            let copy = self.state.clone();
+
+            let copy = self.state;
             if self.state.make_move(r#move).is_err() {
                 continue;
             }
-            let score = -self.quiescence(-beta, -alpha, current_ply+1);
+            num_legal_moves += 1;
+            // Quiet moves are only searched here when they give check
+            if !in_check && is_quiet && !self.state.is_in_check(self.state.to_move) {
+                self.state = copy;
+                continue;
+            }
+            self.update_accumulators(current_ply, &copy);
+            self.move_stack[current_ply] = r#move;
+            let score = -self.quiescence(-beta, -alpha, current_ply+1, false);
             self.state = copy;
             if score >= beta {
+                if self.search_active {
+                    self.tt.store(self.state.hash, score_to_tt(beta, current_ply), 0, Bound::Lower, r#move);
+                }
                 return beta;
             }
             if score > alpha {
+                best_move = r#move;
                 alpha = score;
             }
         }
 
+        // Having skipped nothing, no legal moves while in check can only mean checkmate, which the standing pat already scored
+        if in_check && num_legal_moves == 0 {
+            return alpha;
+        }
+
+        if self.search_active {
+            self.tt.store(self.state.hash, score_to_tt(alpha, current_ply), 0, if alpha > original_alpha { Bound::Exact } else { Bound::Upper }, best_move);
+        }
+
         alpha
     }
 
-    fn sort_moves(&self, move_list: &mut MoveList, ply: usize, in_pv: bool) {
-        move_list.moves[0..move_list.length].sort_by(|a,b| self.score_move(*b, ply, in_pv).cmp(&self.score_move(*a, ply, in_pv)));
+    // Wins and losses are scored by distance to zeroing, just short of the mate scores, since that isn't a real distance to mate
+    fn probe_tablebase(&mut self, current_ply: usize) -> Option<(BitMove, isize)> {
+        let tablebase = self.tablebase.as_ref()?;
+        if count_bits(self.state.occupancy) > 5 {
+            return None;
+        }
+
+        let pos: Chess = self.state.to_fen().parse::<Fen>().unwrap().position(CastlingMode::Standard).unwrap();
+        let tb_result = tablebase.best_move(&pos).unwrap();
+
+        self.tb_hits += 1;
+
+        Some(match tb_result {
+            Some(tb_result) => (
+                encode_move(
+                    tb_result.0.from().unwrap() as usize,
+                    tb_result.0.to() as usize,
+                    Piece::Pawn,
+                    tb_result.0.promotion().map(|p| match p {
+                        Role::Pawn => Piece::Pawn,
+                        Role::Knight => Piece::Knight,
+                        Role::Bishop => Piece::Bishop,
+                        Role::Rook => Piece::Rook,
+                        Role::Queen => Piece::Queen,
+                        Role::King => Piece::King
+                    }),
+                    false,
+                    false,
+                    false,
+                    false
+                ),
+                // The DTZ is from the opponent's point of view, after our move
+                match tb_result.1 {
+                    Dtz(x) if x > 0 => -TB_WIN_VALUE + x as isize,
+                    Dtz(x) if x < 0 => TB_WIN_VALUE + x as isize,
+                    _ => 0
+                }
+            ),
+            None => if self.state.is_in_check(self.state.to_move) { (0, -MATE_VALUE + current_ply as isize) } else { (0, 0) }
+        })
+    }
+
+    fn sort_moves(&self, move_list: &mut MoveList, ply: usize, in_pv: bool, tt_move: BitMove) {
+        move_list.moves[0..move_list.length].sort_by_cached_key(|m| Reverse(self.score_move(*m, ply, in_pv, tt_move)));
     }
 
-    fn score_move(&self, r#move: BitMove, ply: usize, in_pv: bool) -> usize {
+    fn score_move(&self, r#move: BitMove, ply: usize, in_pv: bool, tt_move: BitMove) -> isize {
         if in_pv && self.previous_pv.moves[ply] == r#move {
-            return 11000;
+            return 4_000_000;
+        }
+        if r#move == tt_move {
+            return 3_000_000;
         }
 
         if move_is_capture(r#move) {
-            let mut captured_piece = Piece::Pawn;
-            if !move_is_ep(r#move) {
-                // todo - is it quicker to add pawns to the start of this list? (extra iteration, but most captures will be of pawns)
-                for piece in &[Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
-                    if get_bit(self.state.pieces[*piece as usize], move_to(r#move)) {
-                        captured_piece = *piece;
-                        break;
-                    }
-                }
-            }
-    
-            return 6 * (captured_piece as usize) + (5 - move_piece(r#move) as usize) + 10000;
+            // Victims in order of value, then the least valuable attacker first, then by how well the capture has done so far
+            let captured_piece = self.captured_piece(r#move);
+            let mvv_lva = 6 * captured_piece as isize + (5 - move_piece(r#move) as isize);
+            let score = (2 * MAX_HISTORY as isize + 1) * mvv_lva + self.move_history.capture_score(self.state.to_move, r#move, captured_piece);
+
+            // Captures which lose material once the exchange is played out are tried after the quiet moves
+            return if self.state.see(r#move) >= 0 { 2_000_000 + score } else { -2_000_000 + score };
         }
         else if self.killers[ply][0] == r#move {
-            return 9000;
+            return 1_500_000;
         }
         else if self.killers[ply][1] == r#move {
-            return 8000;
+            return 1_400_000;
         }
-        
-        self.history[self.state.to_move as usize][move_from(r#move)][move_to(r#move)]
+        else if self.move_history.counter_move(self.state.to_move, self.previous_moves(ply)) == r#move {
+            return 1_300_000;
+        }
+
+        self.move_history.quiet_score(self.state.to_move, r#move, self.previous_moves(ply))
+    }
+
+    // From the point of view of the side to move, with the network if we have one
+    fn evaluate(&self, ply: usize) -> isize {
+        match &self.network {
+            Some(network) => network.evaluate(&self.accumulators[ply], self.state.to_move),
+            None => relative_eval(&self.state)
+        }
+    }
+
+    // Works out the next ply's accumulators, having just made a move from the given position
+    fn update_accumulators(&mut self, ply: usize, previous_state: &State) {
+        if let Some(network) = &self.network {
+            let mut accumulators = self.accumulators[ply];
+            network.update_accumulators(&mut accumulators, previous_state, &self.state);
+            self.accumulators[ply + 1] = accumulators;
+        }
+    }
+
+    // How many plies to extend the search of a move by, having just made it. The position before the move is passed in as well
+    fn extension(&self, r#move: BitMove, current_ply: usize, pv_node: bool, singular: bool, previous_state: &State) -> usize {
+        let previous_move = if current_ply > 0 { self.move_stack[current_ply - 1] } else { 0 };
+
+        (extension_allowed(self.path_extensions[current_ply], current_ply, self.root_depth) && extends(r#move, previous_move, previous_state, &self.state, pv_node, singular)) as usize
+    }
+
+    fn captured_piece(&self, r#move: BitMove) -> Piece {
+        if move_is_ep(r#move) { Piece::Pawn } else { self.state.piece_at(move_to(r#move)).unwrap() }
+    }
+
+    fn previous_moves(&self, ply: usize) -> PreviousMoves {
+        [
+            if ply >= 1 { self.move_stack[ply - 1] } else { 0 },
+            if ply >= 2 { self.move_stack[ply - 2] } else { 0 }
+        ]
     }
 }
\ No newline at end of file
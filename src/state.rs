@@ -6,9 +6,15 @@ use crate::castling::{CastleType, decode_castling};
 use crate::attacks::{PAWN_ATTACKS,KNIGHT_ATTACKS,bishop_attacks,rook_attacks,queen_attacks,KING_ATTACKS};
 use crate::moves::{BitMove, move_from, move_to, move_piece, move_is_capture, move_promotion_piece, move_is_double_push, move_is_ep, move_is_castle};
 use crate::zobrist;
+use crate::eval::piece_square_value;
+use crate::default_params::PARAMS;
 use crate::errors::{InvalidFenError, IllegalMoveError};
+use std::cmp::max;
 use std::fmt;
 
+// Kept simple for exchanges, the king being worth more than everything else put together
+pub const SEE_VALUES: [isize; 6] = [100, 300, 300, 500, 900, 20000];
+
 #[derive(Clone, Copy)]
 pub struct History {
     hashes: [u64; 255],
@@ -24,6 +30,11 @@ impl History {
     }
 
     pub fn push(&mut self, hash: u64) {
+        // Only a game breaking the fifty move rule could fill this up, at which point the oldest positions no longer matter
+        if self.length == self.hashes.len() {
+            self.hashes.copy_within(1.., 0);
+            self.length -= 1;
+        }
         self.hashes[self.length] = hash;
         self.length += 1;
     }
@@ -48,7 +59,13 @@ pub struct State {
     pub halfmove_clock: u8,
     pub fullmove_number: u16,
     pub hash: u64,
-    pub history: History
+    // A Zobrist key of the pawns alone, for the pawn hash table
+    pub pawn_hash: u64,
+    pub history: History,
+    // The material and piece-square totals from White's point of view, and the game phase, kept up to date as pieces move
+    pub mg_score: isize,
+    pub eg_score: isize,
+    pub phase: isize
 }
 
 impl State {
@@ -64,7 +81,11 @@ impl State {
             halfmove_clock: 0,
             fullmove_number: 1,
             hash: 0,
-            history: History::new()
+            pawn_hash: 0,
+            history: History::new(),
+            mg_score: 0,
+            eg_score: 0,
+            phase: 0
         }
     }
 
@@ -82,7 +103,7 @@ impl State {
             if c == '/' {
                 sq -= 16;
             }
-            else if c.is_digit(10) {
+            else if c.is_ascii_digit() {
                 sq += c.to_digit(10).unwrap() as usize;
             }
             else {
@@ -105,6 +126,7 @@ impl State {
                 state.colours[colour as usize] = set_bit(state.colours[colour as usize], sq);
                 state.squares[sq] = Some((colour, piece));
                 state.hash ^= zobrist::PIECES[colour as usize][piece as usize][sq];
+                state.add_piece_value(colour, piece, sq);
 
                 sq += 1;
             }
@@ -156,6 +178,7 @@ impl State {
         Ok(state)
     }
 
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_fen(&self) -> String {
         let mut fen = String::new();
 
@@ -251,6 +274,81 @@ impl State {
         | (KING_ATTACKS[sq] & self.pieces[Piece::King as usize] & colour_bb != 0)
     }
 
+    // Every piece of either colour attacking the square, given the occupancy (which may have had pieces taken out of it)
+    pub fn attackers_to(&self, sq: usize, occupancy: u64) -> u64 {
+        let bishops = self.pieces[Piece::Bishop as usize] | self.pieces[Piece::Queen as usize];
+        let rooks = self.pieces[Piece::Rook as usize] | self.pieces[Piece::Queen as usize];
+
+        (PAWN_ATTACKS[sq][Colour::Black as usize] & self.pieces[Piece::Pawn as usize] & self.colours[Colour::White as usize])
+        | (PAWN_ATTACKS[sq][Colour::White as usize] & self.pieces[Piece::Pawn as usize] & self.colours[Colour::Black as usize])
+        | (KNIGHT_ATTACKS[sq] & self.pieces[Piece::Knight as usize])
+        | (bishop_attacks(sq, occupancy) & bishops)
+        | (rook_attacks(sq, occupancy) & rooks)
+        | (KING_ATTACKS[sq] & self.pieces[Piece::King as usize])
+    }
+
+    pub fn piece_at(&self, sq: usize) -> Option<Piece> {
+        self.squares[sq].map(|(_, piece)| piece)
+    }
+
+    // Static exchange evaluation: the material won by the side to move if the move is followed by every capture back and forth on the same square,
+    // each side being free to stop capturing whenever carrying on would lose them material
+    pub fn see(&self, r#move: BitMove) -> isize {
+        let from = move_from(r#move);
+        let to = move_to(r#move);
+        let bishops = self.pieces[Piece::Bishop as usize] | self.pieces[Piece::Queen as usize];
+        let rooks = self.pieces[Piece::Rook as usize] | self.pieces[Piece::Queen as usize];
+
+        let mut gain = [0; 32];
+        let mut occupancy = self.occupancy ^ (1 << from);
+        let mut attacker = move_piece(r#move);
+        if move_is_ep(r#move) {
+            gain[0] = SEE_VALUES[Piece::Pawn as usize];
+            occupancy ^= 1 << (if self.to_move == Colour::White { to - 8 } else { to + 8 });
+        }
+        else if let Some(piece) = self.piece_at(to) {
+            gain[0] = SEE_VALUES[piece as usize];
+        }
+        if let Some(promotion_piece) = move_promotion_piece(r#move) {
+            gain[0] += SEE_VALUES[promotion_piece as usize] - SEE_VALUES[Piece::Pawn as usize];
+            attacker = promotion_piece;
+        }
+
+        let mut attackers = self.attackers_to(to, occupancy) & occupancy;
+        let mut side = !self.to_move;
+        let mut depth = 0;
+        loop {
+            depth += 1;
+            // What the side that just captured stands to gain if the piece they captured with gets taken in turn
+            gain[depth] = SEE_VALUES[attacker as usize] - gain[depth - 1];
+            if max(-gain[depth - 1], gain[depth]) < 0 || depth == gain.len() - 1 {
+                break;
+            }
+
+            let side_attackers = attackers & self.colours[side as usize];
+            let next = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King].iter().find_map(|&piece| {
+                get_ls1b(side_attackers & self.pieces[piece as usize]).map(|sq| (piece, sq))
+            });
+            let Some((piece, sq)) = next else {
+                break;
+            };
+
+            // Taking the piece off the board may uncover a slider behind it
+            occupancy ^= 1 << sq;
+            attackers |= (bishop_attacks(to, occupancy) & bishops) | (rook_attacks(to, occupancy) & rooks);
+            attackers &= occupancy;
+            attacker = piece;
+            side = !side;
+        }
+
+        // The last entry is only what would be gained if someone could recapture, so isn't part of the result
+        for d in (1..depth).rev() {
+            gain[d - 1] = -max(-gain[d - 1], gain[d]);
+        }
+
+        gain[0]
+    }
+
     pub fn is_in_check(&self, colour: Colour) -> bool {
         let king_sq = get_ls1b(self.colours[colour as usize] & self.pieces[Piece::King as usize]).unwrap();
 
@@ -258,7 +356,7 @@ impl State {
     }
 
     pub fn make_move(&mut self, r#move: BitMove) -> Result<(), IllegalMoveError> {
-        let copy = self.clone();
+        let copy = *self;
 
         let from = move_from(r#move);
         let to = move_to(r#move);
@@ -270,6 +368,7 @@ impl State {
         let is_castle = move_is_castle(r#move);
 
         self.squares[from] = None;
+        self.remove_piece_value(self.to_move, piece, from);
 
         self.hash ^= zobrist::PIECES[self.to_move as usize][piece as usize][from];
         if let Some(sq) = self.ep_target {
@@ -281,6 +380,7 @@ impl State {
                 if get_bit(self.pieces[*piece as usize], to) {
                     self.hash ^= zobrist::PIECES[!self.to_move as usize][*piece as usize][to];
                     self.pieces[*piece as usize] = clear_bit(self.pieces[*piece as usize], to);
+                    self.remove_piece_value(!self.to_move, *piece, to);
                     break;
                 }
             }
@@ -292,11 +392,13 @@ impl State {
             self.hash ^= zobrist::PIECES[self.to_move as usize][promotion_piece as usize][to];
             self.pieces[promotion_piece as usize] = set_bit(self.pieces[promotion_piece as usize], to);
             self.squares[to] = Some((self.to_move, promotion_piece));
+            self.add_piece_value(self.to_move, promotion_piece, to);
         }
         else {
             self.hash ^= zobrist::PIECES[self.to_move as usize][piece as usize][to];
             self.pieces[piece as usize] = set_bit(self.pieces[piece as usize], to);
             self.squares[to] = Some((self.to_move, piece));
+            self.add_piece_value(self.to_move, piece, to);
         }
 
         self.colours[self.to_move as usize] = clear_bit(self.colours[self.to_move as usize], from);
@@ -312,6 +414,7 @@ impl State {
             self.pieces[Piece::Pawn as usize] = clear_bit(self.pieces[Piece::Pawn as usize], captured_pawn_sq);
             self.colours[!self.to_move as usize] = clear_bit(self.colours[!self.to_move as usize], captured_pawn_sq);
             self.squares[captured_pawn_sq] = None;
+            self.remove_piece_value(!self.to_move, Piece::Pawn, captured_pawn_sq);
         }
         
         self.ep_target = match is_double_push {
@@ -334,6 +437,8 @@ impl State {
                     self.colours[Colour::White as usize] = set_bit(self.colours[Colour::White as usize], 5);
                     self.squares[7] = None;
                     self.squares[5] = Some((Colour::White, Piece::Rook));
+                    self.remove_piece_value(Colour::White, Piece::Rook, 7);
+                    self.add_piece_value(Colour::White, Piece::Rook, 5);
                 },
                 2 => {
                     self.pieces[Piece::Rook as usize] = clear_bit(self.pieces[Piece::Rook as usize], 0);
@@ -342,6 +447,8 @@ impl State {
                     self.colours[Colour::White as usize] = set_bit(self.colours[Colour::White as usize], 3);
                     self.squares[0] = None;
                     self.squares[3] = Some((Colour::White, Piece::Rook));
+                    self.remove_piece_value(Colour::White, Piece::Rook, 0);
+                    self.add_piece_value(Colour::White, Piece::Rook, 3);
                 },
                 62 => {
                     self.pieces[Piece::Rook as usize] = clear_bit(self.pieces[Piece::Rook as usize], 63);
@@ -350,6 +457,8 @@ impl State {
                     self.colours[Colour::Black as usize] = set_bit(self.colours[Colour::Black as usize], 61);
                     self.squares[63] = None;
                     self.squares[61] = Some((Colour::Black, Piece::Rook));
+                    self.remove_piece_value(Colour::Black, Piece::Rook, 63);
+                    self.add_piece_value(Colour::Black, Piece::Rook, 61);
                 },
                 58 => {
                     self.pieces[Piece::Rook as usize] = clear_bit(self.pieces[Piece::Rook as usize], 56);
@@ -358,6 +467,8 @@ impl State {
                     self.colours[Colour::Black as usize] = set_bit(self.colours[Colour::Black as usize], 59);
                     self.squares[56] = None;
                     self.squares[59] = Some((Colour::Black, Piece::Rook));
+                    self.remove_piece_value(Colour::Black, Piece::Rook, 56);
+                    self.add_piece_value(Colour::Black, Piece::Rook, 59);
                 },
                 _ => panic!("Invalid castle move")
             }
@@ -413,18 +524,73 @@ impl State {
         Ok(())
     }
 
-    pub fn is_repetition(&self) -> bool {
-        if self.history.length == 0 {
-            return false;
+    fn add_piece_value(&mut self, colour: Colour, piece: Piece, sq: usize) {
+        let (mg, eg) = piece_square_value(&PARAMS, colour, piece, sq);
+        self.mg_score += mg;
+        self.eg_score += eg;
+        self.phase += PARAMS.phase_increments[piece as usize];
+        if piece == Piece::Pawn {
+            self.pawn_hash ^= zobrist::PIECES[colour as usize][piece as usize][sq];
         }
+    }
 
-        for i in (0..self.history.length-1).rev().step_by(2) {
-            if self.history.hashes[i] == self.hash {
-                return true;
+    fn remove_piece_value(&mut self, colour: Colour, piece: Piece, sq: usize) {
+        let (mg, eg) = piece_square_value(&PARAMS, colour, piece, sq);
+        self.mg_score -= mg;
+        self.eg_score -= eg;
+        self.phase -= PARAMS.phase_increments[piece as usize];
+        if piece == Piece::Pawn {
+            self.pawn_hash ^= zobrist::PIECES[colour as usize][piece as usize][sq];
+        }
+    }
+
+    // Passes the turn to the opponent without moving, for null move pruning
+    pub fn make_null_move(&mut self) {
+        if let Some(sq) = self.ep_target {
+            self.hash ^= zobrist::EP_FILE[sq_file(sq)];
+        }
+        self.ep_target = None;
+
+        self.halfmove_clock += 1;
+        if self.to_move == Colour::Black {
+            self.fullmove_number += 1;
+        }
+
+        self.to_move = !self.to_move;
+        self.hash ^= zobrist::WHITE_MOVE;
+        // A position reached after a null move can't be a genuine repetition of anything played before it
+        self.history.clear();
+    }
+
+    // Repeating a position from within the search is enough to treat it as a draw, since whoever could avoid the repetition would have done so.
+    // A position from before the search root has to have been repeated already, though, since the game really is only drawn on the third occurrence
+    pub fn is_repetition(&self, plies_from_root: usize) -> bool {
+        let mut count = 0;
+        for distance in (2..=self.history.length).step_by(2) {
+            if self.history.hashes[self.history.length - distance] == self.hash {
+                if distance < plies_from_root {
+                    return true;
+                }
+                count += 1;
+                if count == 2 {
+                    return true;
+                }
             }
         }
 
-        return false;
+        false
+    }
+
+    // Neither side has enough material left to deliver mate, however badly the other plays
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy_pieces = self.pieces[Piece::Pawn as usize] | self.pieces[Piece::Rook as usize] | self.pieces[Piece::Queen as usize];
+        let minor_pieces = self.pieces[Piece::Knight as usize] | self.pieces[Piece::Bishop as usize];
+
+        heavy_pieces == 0 && minor_pieces.count_ones() <= 1
+    }
+
+    pub fn is_draw(&self, plies_from_root: usize) -> bool {
+        self.halfmove_clock >= 100 || self.is_insufficient_material() || self.is_repetition(plies_from_root)
     }
 }
 
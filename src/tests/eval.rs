@@ -1,5 +1,7 @@
 use crate::state::State;
-use crate::eval::eval;
+use crate::eval::{eval, material_and_psts, evaluate_pieces};
+use crate::default_params::PARAMS;
+use crate::moves::generate_moves;
 use more_asserts::assert_lt;
 
 #[test]
@@ -36,4 +38,70 @@ fn test_material_differences() {
         assert_lt!(eval, previous_evaluation);
         previous_evaluation = eval;
     }
+}
+
+fn check_incremental(state: &State, depth: usize) {
+    assert_eq!((state.mg_score, state.eg_score, state.phase), material_and_psts(state, &PARAMS));
+    assert_eq!(state.pawn_hash, State::from_fen(&state.to_fen()).unwrap().pawn_hash);
+    if depth == 0 {
+        return;
+    }
+    for r#move in generate_moves(state) {
+        let mut child = *state;
+        if child.make_move(r#move).is_ok() {
+            check_incremental(&child, depth - 1);
+        }
+    }
+}
+
+#[test]
+fn incremental_eval_matches_from_scratch() {
+    // Between them these cover captures, promotions with and without capture, en passant and castling on both sides
+    check_incremental(&State::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(), 3);
+    check_incremental(&State::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap(), 3);
+}
+
+fn pieces(fen: &str) -> (isize, isize) {
+    evaluate_pieces(&State::from_fen(fen).unwrap(), &PARAMS)
+}
+
+// The same position with the board flipped and the colours swapped. En passant squares aren't handled
+fn mirror_fen(fen: &str) -> String {
+    let fields: Vec<&str> = fen.split(' ').collect();
+    let swap_case = |s: &str| s.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>();
+    let board = fields[0].split('/').rev().map(swap_case).collect::<Vec<String>>().join("/");
+    let to_move = if fields[1] == "w" { "b" } else { "w" };
+    format!("{} {} {} - {} {}", board, to_move, swap_case(fields[2]), fields[4], fields[5])
+}
+
+#[test]
+fn piece_terms_are_symmetric() {
+    let fen = "r1b1k2r/pp3ppp/2n1pn2/q2p4/1bPP4/2N1PN2/PPQB1PPP/R3KB1R w KQkq - 0 8";
+    let (mg, eg) = pieces(fen);
+    let (mirrored_mg, mirrored_eg) = pieces(&mirror_fen(fen));
+    assert_eq!((mg, eg), (-mirrored_mg, -mirrored_eg));
+}
+
+#[test]
+fn piece_placement() {
+    // Rooks want open files
+    assert!(pieces("4k3/8/8/8/8/8/1P6/R3K3 w - - 0 1") > pieces("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1"));
+    // Knights want outposts which no pawn can chase them away from
+    assert!(pieces("4k3/7p/8/4N3/3P4/8/8/4K3 w - - 0 1") > pieces("4k3/5p2/8/4N3/3P4/8/8/4K3 w - - 0 1"));
+    // A bishop shut in on a7 is as good as lost
+    assert!(pieces("4k3/B7/2p5/8/8/8/8/4K3 w - - 0 1").0 > pieces("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1").0 + 50);
+    // A rook boxed in by its king is only trapped once the king can no longer castle to free it
+    assert_eq!(pieces("4k3/8/8/8/8/8/6PP/4K2R w K - 0 1").0, pieces("4k3/8/8/8/8/8/6PP/4K2R w Q - 0 1").0 - PARAMS.trapped_rook.0);
+    assert_eq!(pieces("4k3/8/8/8/8/8/6PP/4K2R w K - 0 1"), pieces("4k3/8/8/8/8/8/6PP/4K2R w KQ - 0 1"));
+    // Two bishops are better than one
+    assert!(pieces("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").1 > 2 * pieces("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1").1);
+}
+
+#[test]
+fn eval_is_symmetric() {
+    for fen in &["r1b1k2r/pp3ppp/2n1pn2/q2p4/1bPP4/2N1PN2/PPQB1PPP/R3KB1R w KQkq - 0 8", "2kr3r/ppp2ppp/2n5/4q3/6b1/2NP1N2/PPP2PPP/R2Q1RK1 b - - 3 12"] {
+        let state = State::from_fen(fen).unwrap();
+        let mirrored = State::from_fen(&mirror_fen(fen)).unwrap();
+        assert_eq!(eval(&state), -eval(&mirrored));
+    }
 }
\ No newline at end of file
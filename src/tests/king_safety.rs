@@ -0,0 +1,37 @@
+use crate::state::State;
+use crate::king_safety::evaluate_king_safety;
+use crate::default_params::PARAMS;
+
+fn king_safety(fen: &str) -> (isize, isize) {
+    evaluate_king_safety(&State::from_fen(fen).unwrap(), &PARAMS)
+}
+
+#[test]
+fn start_position_is_balanced() {
+    assert_eq!(king_safety("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), (0, 0));
+}
+
+#[test]
+fn pawn_shield() {
+    // Pushing the pawns in front of the king weakens it, and losing them altogether is worse still
+    let intact = king_safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").0;
+    let pushed = king_safety("6k1/5ppp/8/8/8/5PPP/8/6K1 w - - 0 1").0;
+    let missing = king_safety("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1").0;
+    assert!(intact > pushed && pushed > missing);
+}
+
+#[test]
+fn pawn_storm() {
+    // Enemy pawns bearing down on the king are more dangerous the closer they get
+    let far = king_safety("6k1/8/6p1/8/8/8/5PPP/6K1 w - - 0 1").0;
+    let near = king_safety("6k1/8/8/8/8/6p1/5PPP/6K1 w - - 0 1").0;
+    assert!(near < far);
+}
+
+#[test]
+fn king_zone_attacks() {
+    // The same pieces are far more dangerous swarming around the king than sitting on the other side of the board
+    let attacked = king_safety("1k6/8/8/8/6q1/5n2/5PPP/3r2K1 w - - 0 1").0;
+    let quiet = king_safety("1k1r4/q7/2n5/8/8/8/5PPP/6K1 w - - 0 1").0;
+    assert!(attacked < quiet - 50);
+}
\ No newline at end of file
@@ -5,4 +5,22 @@ mod perft;
 #[cfg(test)]
 mod uci;
 #[cfg(test)]
-mod search;
\ No newline at end of file
+mod search;
+#[cfg(test)]
+mod options;
+#[cfg(test)]
+mod tt;
+#[cfg(test)]
+mod state;
+#[cfg(test)]
+mod time;
+#[cfg(test)]
+mod ordering;
+#[cfg(test)]
+mod nnue;
+#[cfg(test)]
+mod pawns;
+#[cfg(test)]
+mod king_safety;
+#[cfg(test)]
+mod tuner;
\ No newline at end of file
@@ -0,0 +1,75 @@
+use crate::state::State;
+use crate::nnue::{Network, Accumulator, HIDDEN_SIZE};
+use crate::search::Search;
+use crate::limits::SearchLimits;
+use crate::moves::generate_moves;
+use std::sync::Arc;
+
+// Small made up weights in a repeating pattern, which are enough to check that the arithmetic is consistent
+pub fn test_network_bytes() -> Vec<u8> {
+    let len = 768 * HIDDEN_SIZE + HIDDEN_SIZE + 2 * HIDDEN_SIZE + 1;
+    (0..len).flat_map(|i| (((i * 37) % 61) as i16 - 30).to_le_bytes().to_vec()).collect()
+}
+
+fn test_network() -> Network {
+    Network::from_bytes(&test_network_bytes()).unwrap()
+}
+
+fn check_accumulators(network: &Network, state: &State, accumulators: &[Accumulator; 2], depth: usize) {
+    assert_eq!(*accumulators, network.accumulators(state));
+    if depth == 0 {
+        return;
+    }
+    for r#move in generate_moves(state) {
+        let mut child = *state;
+        if child.make_move(r#move).is_ok() {
+            let mut child_accumulators = *accumulators;
+            network.update_accumulators(&mut child_accumulators, state, &child);
+            check_accumulators(network, &child, &child_accumulators, depth - 1);
+        }
+    }
+}
+
+#[test]
+fn rejects_wrong_size() {
+    assert!(Network::from_bytes(&[0; 100]).is_none());
+
+    let mut bytes = test_network_bytes();
+    bytes.extend_from_slice(&[0; 64]);
+    assert!(Network::from_bytes(&bytes).is_none());
+
+    // Padding up to a 64 byte boundary is fine
+    bytes.truncate(bytes.len() - 2);
+    assert!(Network::from_bytes(&bytes).is_some());
+}
+
+#[test]
+fn incremental_accumulators_match_from_scratch() {
+    let network = test_network();
+    for fen in &["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1"] {
+        let state = State::from_fen(fen).unwrap();
+        check_accumulators(&network, &state, &network.accumulators(&state), 3);
+    }
+}
+
+#[test]
+fn symmetric_eval() {
+    // The same position with the colours swapped must look the same to the side to move
+    let network = test_network();
+    let state = State::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    let mirrored = State::from_fen("rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1").unwrap();
+    assert_eq!(network.evaluate_state(&state), network.evaluate_state(&mirrored));
+}
+
+#[test]
+fn search_with_network() {
+    let state = State::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let mut limits = SearchLimits::new();
+    limits.depth = Some(4);
+    let mut search = Search::new(state, &None);
+    search.set_limits(limits);
+    search.set_threads(2);
+    search.set_network(Some(Arc::new(test_network())));
+    let result = search.go();
+    assert!(generate_moves(&state).any(|r#move| r#move == result.0));
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+use crate::options::Options;
+
+#[test]
+fn set_spin() {
+    let mut options = Options::new();
+
+    assert_eq!(options.set("Move Overhead", Some("30")).unwrap(), "Move Overhead");
+    assert_eq!(options.spin("Move Overhead"), 30);
+
+    // Out of range values are clamped rather than rejected
+    options.set("Move Overhead", Some("100000")).unwrap();
+    assert_eq!(options.spin("Move Overhead"), 5000);
+
+    assert!(options.set("Move Overhead", Some("thirty")).is_err());
+    assert_eq!(options.spin("Move Overhead"), 5000);
+}
+
+#[test]
+fn set_check() {
+    let mut options = Options::new();
+
+    options.set("OwnBook", Some("false")).unwrap();
+    assert!(!options.check("OwnBook"));
+    assert!(options.set("OwnBook", Some("maybe")).is_err());
+}
+
+#[test]
+fn set_string() {
+    let mut options = Options::new();
+
+    assert_eq!(options.string("SyzygyPath"), None);
+    options.set("SyzygyPath", Some("/tmp/syzygy")).unwrap();
+    assert_eq!(options.string("SyzygyPath"), Some("/tmp/syzygy".to_string()));
+    options.set("SyzygyPath", Some("<empty>")).unwrap();
+    assert_eq!(options.string("SyzygyPath"), None);
+}
+
+#[test]
+fn names_are_case_insensitive() {
+    let mut options = Options::new();
+
+    assert_eq!(options.set("multipv", Some("3")).unwrap(), "MultiPV");
+    assert_eq!(options.spin("MultiPV"), 3);
+    assert!(options.set("NotAnOption", Some("1")).is_err());
+}
\ No newline at end of file
@@ -0,0 +1,50 @@
+use crate::ordering::{MoveHistory, MAX_HISTORY};
+use crate::state::State;
+use crate::colours::Colour;
+use crate::moves::{generate_moves, move_to_algebraic, BitMove};
+
+fn find_move(state: &State, move_string: &str) -> BitMove {
+    generate_moves(state).find(|m| move_to_algebraic(*m) == move_string).unwrap()
+}
+
+#[test]
+fn history_is_bounded() {
+    let state = State::start_pos();
+    let (good, bad) = (find_move(&state, "g1f3"), find_move(&state, "a2a3"));
+    let mut history = MoveHistory::new();
+    for _ in 0..1000 {
+        history.update_quiets(Colour::White, good, &[bad], [0, 0], 20);
+    }
+
+    assert!(history.quiet_score(Colour::White, good, [0, 0]) <= MAX_HISTORY as isize);
+    assert!(history.quiet_score(Colour::White, bad, [0, 0]) >= -MAX_HISTORY as isize);
+    assert!(history.quiet_score(Colour::White, good, [0, 0]) > history.quiet_score(Colour::White, bad, [0, 0]));
+}
+
+#[test]
+fn counter_moves_and_continuations() {
+    let mut state = State::start_pos();
+    let previous_move = find_move(&state, "e2e4");
+    state.make_move(previous_move).unwrap();
+    let reply = find_move(&state, "c7c5");
+
+    let mut history = MoveHistory::new();
+    history.update_quiets(Colour::Black, reply, &[], [previous_move, 0], 4);
+
+    assert_eq!(history.counter_move(Colour::Black, [previous_move, 0]), reply);
+    // The continuation history adds to the plain history only when following the same move
+    assert!(history.quiet_score(Colour::Black, reply, [previous_move, 0]) > history.quiet_score(Colour::Black, reply, [0, 0]));
+}
+
+#[test]
+fn ageing_halves_history() {
+    let state = State::start_pos();
+    let (good, bad) = (find_move(&state, "g1f3"), find_move(&state, "a2a3"));
+    let mut history = MoveHistory::new();
+    history.update_quiets(Colour::White, good, &[bad], [0, 0], 4);
+    let (good_score, bad_score) = (history.quiet_score(Colour::White, good, [0, 0]), history.quiet_score(Colour::White, bad, [0, 0]));
+
+    history.age();
+    assert_eq!(history.quiet_score(Colour::White, good, [0, 0]), good_score / 2);
+    assert_eq!(history.quiet_score(Colour::White, bad, [0, 0]), bad_score / 2);
+}
\ No newline at end of file
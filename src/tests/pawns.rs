@@ -0,0 +1,81 @@
+use crate::state::State;
+use crate::colours::Colour;
+use crate::pawns::{is_passed, evaluate_pawns, evaluate_pawns_with};
+use crate::helpers::algebraic_to_sq;
+use crate::params::EvalParams;
+use crate::default_params::PARAMS;
+
+fn pawns(fen: &str) -> (isize, isize) {
+    evaluate_pawns(&State::from_fen(fen).unwrap())
+}
+
+// How much of the pawn score comes from the terms which the given change to the parameters turns off
+fn term(fen: &str, without_term: fn(&mut EvalParams)) -> (isize, isize) {
+    let state = State::from_fen(fen).unwrap();
+    let mut params = PARAMS.clone();
+    without_term(&mut params);
+    let ((mg, eg), (without_mg, without_eg)) = (evaluate_pawns_with(&state, &PARAMS), evaluate_pawns_with(&state, &params));
+    (mg - without_mg, eg - without_eg)
+}
+
+#[test]
+fn passed_pawns() {
+    let state = State::from_fen("4k3/8/1p6/8/P1P4P/8/8/4K3 w - - 0 1").unwrap();
+    assert!(!is_passed(&state, algebraic_to_sq("a4"), Colour::White));
+    assert!(!is_passed(&state, algebraic_to_sq("c4"), Colour::White));
+    assert!(is_passed(&state, algebraic_to_sq("h4"), Colour::White));
+    assert!(!is_passed(&state, algebraic_to_sq("b6"), Colour::Black));
+}
+
+#[test]
+fn symmetric() {
+    let (mg, eg) = pawns("4k3/p4ppp/1p6/2P5/8/4P3/P4P1P/4K3 w - - 0 1");
+    let (mirrored_mg, mirrored_eg) = pawns("4k3/p4p1p/4p3/8/2p5/1P6/P4PPP/4K3 b - - 0 1");
+    assert_eq!((mg, eg), (-mirrored_mg, -mirrored_eg));
+}
+
+#[test]
+fn weak_pawns() {
+    // Doubled and isolated pawns are worse than the same number of pawns side by side
+    let (healthy_mg, healthy_eg) = pawns("4k3/8/8/8/8/8/2PP4/4K3 w - - 0 1");
+    let (weak_mg, weak_eg) = pawns("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1");
+    assert!(weak_mg < healthy_mg && weak_eg < healthy_eg);
+
+    // Likewise a backward pawn compared to one which can still be supported
+    let (_, backward_eg) = pawns("4k3/8/8/8/1P1p4/8/2P5/4K3 w - - 0 1");
+    let (_, supported_eg) = pawns("4k3/8/8/8/1P1p4/2P5/8/4K3 w - - 0 1");
+    assert!(backward_eg < supported_eg);
+}
+
+#[test]
+fn passed_pawn_king_proximity() {
+    // A passed pawn is worth more in the endgame when the enemy king is too far away to stop it
+    let (_, near) = pawns("8/2k5/8/1P6/8/8/8/6K1 w - - 0 1");
+    let (_, far) = pawns("8/7k/8/1P6/8/8/8/6K1 w - - 0 1");
+    assert!(far > near);
+}
+
+#[test]
+fn connected_pawns() {
+    let without_connected = |params: &mut EvalParams| params.connected = [0; 8];
+    // Side by side, both pawns count as connected
+    assert_eq!(term("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1", without_connected), (2 * PARAMS.connected[3], 2 * PARAMS.connected[3]));
+    // Whereas only the front pawn of a chain is, being the one that's defended
+    assert_eq!(term("4k3/8/8/8/3P4/4P3/8/4K3 w - - 0 1", without_connected), (PARAMS.connected[3], PARAMS.connected[3]));
+    assert_eq!(term("4k3/8/8/8/8/4p3/3p4/4K3 b - - 0 1", without_connected), (-PARAMS.connected[6], -PARAMS.connected[6]));
+    assert_eq!(term("4k3/8/8/8/2P1P3/8/8/4K3 w - - 0 1", without_connected), (0, 0));
+}
+
+#[test]
+fn candidate_pawns() {
+    let without_candidates = |params: &mut EvalParams| {
+        params.candidate_mg = [0; 8];
+        params.candidate_eg = [0; 8];
+    };
+    // The c-pawn has a clear file ahead of it, and the b-pawn to help it past the d-pawn
+    assert_eq!(term("4k3/8/3p4/8/1PP5/8/8/4K3 w - - 0 1", without_candidates), (PARAMS.candidate_mg[3], PARAMS.candidate_eg[3]));
+    // Without the helper it's outnumbered
+    assert_eq!(term("4k3/8/3p4/8/2P5/8/8/4K3 w - - 0 1", without_candidates), (0, 0));
+    // With enemy pawns in front of them neither of White's files is clear, leaving Black's d-pawn as the only candidate
+    assert_eq!(term("4k3/8/1ppp4/8/1PP5/8/8/4K3 w - - 0 1", without_candidates), (-PARAMS.candidate_mg[2], -PARAMS.candidate_eg[2]));
+}
\ No newline at end of file
@@ -1,12 +1,22 @@
 use crate::state::State;
-use crate::search::Search;
-use crate::moves::move_to_algebraic;
+use crate::search::{Search, PruningMargins, mate_in, extends, extension_allowed, MAX_PATH_EXTENSIONS};
+use crate::limits::SearchLimits;
+use crate::moves::{generate_moves, move_to_algebraic, BitMove};
+use crate::colours::Colour;
+use crate::tt::TranspositionTable;
+use std::sync::{Arc, Mutex};
+
+fn depth_limit(depth: usize) -> SearchLimits {
+    let mut limits = SearchLimits::new();
+    limits.depth = Some(depth);
+    limits
+}
 
 #[test]
 fn mates_in_1() {
     let state = State::from_fen("r1bq1rk1/pp1nbppp/2n1p3/3pP2Q/2pP4/2P4N/PPBN1PPP/R1B1K2R w KQ - 6 10").unwrap();
     let mut search = Search::new(state, &None);
-    search.set_depth(6);
+    search.set_limits(depth_limit(6));
     let result = search.go();
     assert_eq!(move_to_algebraic(result.0), "h5h7");
 }
@@ -15,7 +25,7 @@ fn mates_in_1() {
 fn mates_in_3() {
     let state = State::from_fen("r5k1/2p2ppp/2q5/7b/2r5/4R1PP/2P1QP2/4R1K1 w - - 0 32").unwrap();
     let mut search = Search::new(state, &None);
-    search.set_depth(6);
+    search.set_limits(depth_limit(6));
     let result = search.go();
     assert_eq!(move_to_algebraic(result.0), "e3e8");
 }
@@ -24,16 +34,219 @@ fn mates_in_3() {
 fn defends_mate_in_3() {
     let state = State::from_fen("r5k1/2p2ppp/2q5/8/2r5/4R1PP/2P1QP2/4R1K1 b - - 0 1").unwrap();
     let mut search = Search::new(state, &None);
-    search.set_depth(6);
+    search.set_limits(depth_limit(6));
     let result = search.go();
-    assert!(vec!["h7h6".to_string(), "g7g6".to_string(), "g8f8".to_string(), "a8f8".to_string()].contains(&move_to_algebraic(result.0)));
+    assert!(["h7h6".to_string(), "g7g6".to_string(), "g8f8".to_string(), "a8f8".to_string()].contains(&move_to_algebraic(result.0)));
 }
 
 #[test]
 fn tactic_to_win_knight() {
     let state = State::from_fen("r1n4k/P1rq1pb1/1Qp1p2p/3pP1p1/3P4/5NP1/R2B1P1P/R5K1 w - - 6 32").unwrap();
     let mut search = Search::new(state, &None);
-    search.set_depth(6);
+    search.set_limits(depth_limit(6));
     let result = search.go();
     assert_eq!(move_to_algebraic(result.0), "b6b8");
+}
+
+#[test]
+fn pruning_keeps_shallow_tactics() {
+    // Even with margins far tighter than the defaults, the static eval mustn't prune away the knight being won
+    for margins in &[PruningMargins::default(), PruningMargins { reverse_futility: 20, razoring: 50, futility_base: 20, futility: 20 }] {
+        let state = State::from_fen("r1n4k/P1rq1pb1/1Qp1p2p/3pP1p1/3P4/5NP1/R2B1P1P/R5K1 w - - 6 32").unwrap();
+        let mut search = Search::new(state, &None);
+        search.set_limits(depth_limit(6));
+        search.set_pruning_margins(*margins);
+        let result = search.go();
+        assert_eq!(move_to_algebraic(result.0), "b6b8");
+    }
+}
+
+#[test]
+fn multi_pv() {
+    // Capturing the queen is clearly best, so it must head the first line with every other line being distinct and no better
+    let state = State::from_fen("rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(4));
+    search.set_multi_pv(3);
+    search.go();
+
+    let lines = search.pv_lines();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(move_to_algebraic(lines[0].pv.moves[0]), "h5g5");
+    assert_ne!(lines[1].pv.moves[0], lines[0].pv.moves[0]);
+    assert_ne!(lines[2].pv.moves[0], lines[0].pv.moves[0]);
+    assert_ne!(lines[2].pv.moves[0], lines[1].pv.moves[0]);
+    assert!(lines[0].score >= lines[1].score && lines[1].score >= lines[2].score);
+}
+
+#[test]
+fn multi_pv_with_one_legal_move() {
+    // The king's only legal move is to capture the queen, so only one line can be reported however many are asked for
+    let state = State::from_fen("7k/8/8/8/8/8/6q1/7K w - - 0 1").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(3));
+    search.set_multi_pv(4);
+    search.go();
+
+    assert_eq!(search.pv_lines().len(), 1);
+}
+
+#[test]
+fn multithreaded_search() {
+    let state = State::from_fen("r1n4k/P1rq1pb1/1Qp1p2p/3pP1p1/3P4/5NP1/R2B1P1P/R5K1 w - - 6 32").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(6));
+    search.set_threads(4);
+    let result = search.go();
+    assert_eq!(move_to_algebraic(result.0), "b6b8");
+}
+
+#[test]
+fn reports_mate_distance() {
+    // Searching deeper than the mate means reusing TT entries at different plies, which mustn't change the distance
+    let state = State::from_fen("r5k1/2p2ppp/2q5/7b/2r5/4R1PP/2P1QP2/4R1K1 w - - 0 32").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(8));
+    search.go();
+    assert_eq!(mate_in(search.pv_lines()[0].score), Some(3));
+
+    let state = State::from_fen("r3R1k1/2p2ppp/2q5/7b/2r5/6PP/2P1QP2/4R1K1 b - - 1 32").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(8));
+    search.go();
+    assert_eq!(mate_in(search.pv_lines()[0].score), Some(-2));
+}
+
+#[test]
+fn late_quiet_move_survives_reduction() {
+    // Pushing the a-pawn is a quiet move well down the ordering, so it's first searched reduced and has to be searched again at full depth to be found
+    let state = State::from_fen("r1k1r3/2p4R/2p4B/4p3/pQ1pn3/2P5/PP4PP/6K1 b - - 0 34").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(6));
+    let result = search.go();
+    assert_eq!(move_to_algebraic(result.0), "a4a3");
+}
+
+#[test]
+fn wins_queen_with_probcut() {
+    // ProbCut prunes plenty of nodes at this depth, but mustn't lose sight of the queen hanging to the bishop
+    let state = State::from_fen("rnb1kbnr/pppp1ppp/8/4p1q1/3P4/2N5/PPP1PPPP/R1BQKBNR w KQkq - 0 3").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_own_book(false);
+    search.set_limits(depth_limit(8));
+    let result = search.go();
+    assert_eq!(move_to_algebraic(result.0), "c1g5");
+    assert!(result.1 > 600);
+}
+
+#[test]
+fn excluded_root_moves_leave_tt_alone() {
+    // With only one move to search, the second line has nothing left, which mustn't be stored for the root as stalemate
+    let state = State::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    let tt = Arc::new(TranspositionTable::new(1));
+    let mut search = Search::new(state, &None);
+    let mut limits = depth_limit(4);
+    limits.searchmoves = generate_moves(&state).filter(|r#move| move_to_algebraic(*r#move) == "d1d2").collect();
+    search.set_limits(limits);
+    search.set_multi_pv(2);
+    search.set_transposition_table(tt.clone());
+    search.go();
+
+    assert_eq!(search.pv_lines().len(), 1);
+    assert!(tt.probe(state.hash).unwrap().score > 500);
+}
+
+#[test]
+fn perpetual_check_terminates() {
+    // Black's queen can check forever, which used to extend every ply and make a modest depth take far too long
+    let state = State::from_fen("6k1/5ppp/8/8/8/8/q4PPP/3Q2K1 b - - 0 1").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(10));
+    let start = std::time::Instant::now();
+    search.go();
+    assert!(start.elapsed().as_secs() < 30);
+}
+
+#[test]
+fn quiescence_sees_mating_checks() {
+    // Grabbing the pawn abandons the back rank, which only quiet checks and evasions in quiescence can see at depth 1
+    let state = State::from_fen("4r1k1/5ppp/8/p7/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+    let mut search = Search::new(state, &None);
+    search.set_limits(depth_limit(1));
+    let result = search.go();
+    assert_ne!(move_to_algebraic(result.0), "a1a5");
+}
+
+#[test]
+fn move_histories_carry_over_between_searches() {
+    let state = State::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+    let histories = Arc::new(Mutex::new(Vec::new()));
+    let mut search = Search::new(state, &None);
+    search.set_own_book(false);
+    search.set_limits(depth_limit(6));
+    search.set_threads(2);
+    search.set_move_histories(histories.clone());
+    search.go();
+
+    // One set of tables for each thread is handed back, holding what the search learnt
+    let histories = histories.lock().unwrap();
+    assert_eq!(histories.len(), 2);
+    assert!(generate_moves(&state).any(|r#move| histories[0].quiet_score(Colour::White, r#move, [0, 0]) != 0));
+}
+
+// Plays the move, returning it along with the positions before and after
+fn play(fen: &str, move_string: &str) -> (BitMove, State, State) {
+    let before = State::from_fen(fen).unwrap();
+    let r#move = generate_moves(&before).find(|r#move| move_to_algebraic(*r#move) == move_string).unwrap();
+    let mut after = before;
+    after.make_move(r#move).unwrap();
+    (r#move, before, after)
+}
+
+#[test]
+fn singular_moves_are_extended() {
+    let (r#move, before, after) = play("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a2");
+    assert!(!extends(r#move, 0, &before, &after, true, false));
+    assert!(extends(r#move, 0, &before, &after, false, true));
+}
+
+#[test]
+fn checks_are_extended() {
+    let (r#move, before, after) = play("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1a8");
+    assert!(extends(r#move, 0, &before, &after, false, false));
+
+    // Unless they give away the checking piece
+    let (r#move, before, after) = play("4k3/2q5/8/8/8/8/8/3RK3 w - - 0 1", "d1d8");
+    assert!(!extends(r#move, 0, &before, &after, false, false));
+}
+
+#[test]
+fn recaptures_are_extended_in_pv_nodes() {
+    let (previous_move, _, position) = play("4k3/8/4p3/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5");
+    let (r#move, before, after) = play(&position.to_fen(), "e6d5");
+    assert!(extends(r#move, previous_move, &before, &after, true, false));
+    assert!(!extends(r#move, previous_move, &before, &after, false, false));
+    assert!(!extends(r#move, 0, &before, &after, true, false));
+}
+
+#[test]
+fn passed_pawns_pushed_to_the_seventh_are_extended() {
+    let (r#move, before, after) = play("4k3/8/P7/8/8/8/8/4K3 w - - 0 1", "a6a7");
+    assert!(extends(r#move, 0, &before, &after, false, false));
+
+    let (r#move, before, after) = play("4k3/8/8/P7/8/8/8/4K3 w - - 0 1", "a5a6");
+    assert!(!extends(r#move, 0, &before, &after, false, false));
+
+    let (r#move, before, after) = play("4k3/8/8/8/8/p7/8/4K3 b - - 0 1", "a3a2");
+    assert!(extends(r#move, 0, &before, &after, false, false));
+}
+
+#[test]
+fn extensions_are_capped() {
+    assert!(extension_allowed(0, 5, 10));
+    assert!(extension_allowed(MAX_PATH_EXTENSIONS - 1, 5, 10));
+    assert!(!extension_allowed(MAX_PATH_EXTENSIONS, 5, 10));
+    // Nor can a line be extended once it's twice as long as the iteration's depth
+    assert!(extension_allowed(0, 19, 10));
+    assert!(!extension_allowed(0, 20, 10));
 }
\ No newline at end of file
@@ -0,0 +1,85 @@
+use crate::state::State;
+use crate::moves::{generate_moves, move_to_algebraic};
+
+fn see(fen: &str, move_string: &str) -> isize {
+    let state = State::from_fen(fen).unwrap();
+    let r#move = generate_moves(&state).find(|m| move_to_algebraic(*m) == move_string).unwrap();
+    state.see(r#move)
+}
+
+#[test]
+fn null_move() {
+    let mut state = State::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/5N2/PPPP1PPP/RNBQKB1R b KQkq e3 0 3").unwrap();
+    state.make_null_move();
+
+    let expected = State::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 1 4").unwrap();
+    assert_eq!(state.hash, expected.hash);
+    assert_eq!(state.to_fen(), expected.to_fen());
+}
+
+#[test]
+fn null_move_twice_restores_hash() {
+    let mut state = State::from_fen("r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3").unwrap();
+    let hash = state.hash;
+    state.make_null_move();
+    assert_ne!(state.hash, hash);
+    state.make_null_move();
+    assert_eq!(state.hash, hash);
+}
+
+#[test]
+fn see_undefended_pawn() {
+    assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+}
+
+#[test]
+fn see_defended_pawn() {
+    assert_eq!(see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"), -200);
+}
+
+#[test]
+fn see_x_ray() {
+    // The rook on d8 only joins in once the one in front of it has recaptured
+    assert_eq!(see("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), -400);
+}
+
+#[test]
+fn see_quiet_move() {
+    assert_eq!(see("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "d1d5"), -900);
+    assert_eq!(see("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "d1d4"), 0);
+}
+
+#[test]
+fn insufficient_material() {
+    assert!(State::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1").unwrap().is_insufficient_material());
+    assert!(State::from_fen("8/8/4k3/8/8/3KB3/8/8 w - - 0 1").unwrap().is_insufficient_material());
+    assert!(State::from_fen("8/8/4kn2/8/8/3K4/8/8 w - - 0 1").unwrap().is_insufficient_material());
+    assert!(!State::from_fen("8/8/4kn2/8/8/3KB3/8/8 w - - 0 1").unwrap().is_insufficient_material());
+    assert!(!State::from_fen("8/8/4k3/8/8/3KP3/8/8 w - - 0 1").unwrap().is_insufficient_material());
+}
+
+#[test]
+fn fifty_move_rule() {
+    assert!(!State::from_fen("8/8/4k3/8/8/3KR3/8/8 w - - 99 80").unwrap().is_draw(0));
+    assert!(State::from_fen("8/8/4k3/8/8/3KR3/8/8 w - - 100 80").unwrap().is_draw(0));
+}
+
+#[test]
+fn repetition() {
+    let mut state = State::start_pos();
+    for move_string in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+        let r#move = generate_moves(&state).find(|m| move_to_algebraic(*m) == move_string).unwrap();
+        state.make_move(r#move).unwrap();
+    }
+
+    // The start position has occurred twice now, which only counts if the first time was after the search root
+    assert!(!state.is_repetition(0));
+    assert!(!state.is_repetition(4));
+    assert!(state.is_repetition(5));
+
+    for move_string in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+        let r#move = generate_moves(&state).find(|m| move_to_algebraic(*m) == move_string).unwrap();
+        state.make_move(r#move).unwrap();
+    }
+    assert!(state.is_repetition(0));
+}
\ No newline at end of file
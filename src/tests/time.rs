@@ -0,0 +1,43 @@
+use crate::time::TimeManager;
+use crate::limits::SearchLimits;
+use crate::colours::Colour;
+use std::time::Duration;
+
+fn clock(remaining: u64, increment: u64) -> SearchLimits {
+    let mut limits = SearchLimits::new();
+    limits.times = [Some(Duration::from_millis(remaining)); 2];
+    limits.increments = [Some(Duration::from_millis(increment)); 2];
+    limits
+}
+
+#[test]
+fn plenty_of_time() {
+    let time_manager = TimeManager::new(&clock(60000, 1000), Colour::White, Duration::from_millis(10));
+    assert!(!time_manager.soft_limit_reached());
+    assert!(!time_manager.hard_limit_reached());
+}
+
+#[test]
+fn move_overhead_exceeds_remaining_time() {
+    let time_manager = TimeManager::new(&clock(5, 0), Colour::Black, Duration::from_millis(10));
+    assert!(time_manager.soft_limit_reached());
+    assert!(time_manager.hard_limit_reached());
+}
+
+#[test]
+fn forced_move() {
+    let mut time_manager = TimeManager::new(&clock(60000, 1000), Colour::White, Duration::from_millis(10));
+    time_manager.forced_move();
+    assert!(time_manager.soft_limit_reached());
+    assert!(!time_manager.hard_limit_reached());
+}
+
+#[test]
+fn infinite() {
+    let mut limits = clock(0, 0);
+    limits.infinite = true;
+    let mut time_manager = TimeManager::new(&limits, Colour::White, Duration::from_millis(10));
+    time_manager.forced_move();
+    assert!(!time_manager.soft_limit_reached());
+    assert!(!time_manager.hard_limit_reached());
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+use crate::tt::TranspositionTable;
+use crate::search::Bound;
+use crate::moves::encode_move;
+use crate::pieces::Piece;
+
+#[test]
+fn store_and_probe() {
+    let tt = TranspositionTable::new(1);
+    let r#move = encode_move(12, 28, Piece::Pawn, None, false, true, false, false);
+
+    assert!(tt.probe(0x1234_5678_9abc_def0).is_none());
+
+    tt.store(0x1234_5678_9abc_def0, -9995, 7, Bound::Lower, r#move);
+    let entry = tt.probe(0x1234_5678_9abc_def0).unwrap();
+    assert_eq!(entry.score, -9995);
+    assert_eq!(entry.depth, 7);
+    assert_eq!(entry.bound, Bound::Lower);
+    assert_eq!(entry.r#move, r#move);
+
+    // Same bucket, different position
+    assert!(tt.probe(0x1234_5678_9abc_def0 ^ (1 << 63)).is_none());
+}
+
+#[test]
+fn keeps_move_when_none_given() {
+    let tt = TranspositionTable::new(1);
+    let r#move = encode_move(6, 21, Piece::Knight, None, false, false, false, false);
+
+    tt.store(42, 10, 3, Bound::Lower, r#move);
+    tt.store(42, 5, 4, Bound::Upper, 0);
+
+    let entry = tt.probe(42).unwrap();
+    assert_eq!(entry.depth, 4);
+    assert_eq!(entry.bound, Bound::Upper);
+    assert_eq!(entry.r#move, r#move);
+}
+
+#[test]
+fn shallow_exact_entries_keep_deep_ones() {
+    let tt = TranspositionTable::new(1);
+    let r#move = encode_move(6, 21, Piece::Knight, None, false, false, false, false);
+
+    // A quiescence result for the same position mustn't throw away what the main search found
+    tt.store(42, 10, 12, Bound::Lower, r#move);
+    tt.store(42, 5, 0, Bound::Exact, 0);
+
+    let entry = tt.probe(42).unwrap();
+    assert_eq!(entry.depth, 12);
+    assert_eq!(entry.bound, Bound::Lower);
+    assert_eq!(entry.score, 10);
+    assert_eq!(entry.r#move, r#move);
+
+    // Though it can replace the entry from a previous search
+    tt.new_search();
+    tt.store(42, 5, 0, Bound::Exact, 0);
+    assert_eq!(tt.probe(42).unwrap().depth, 0);
+}
+
+#[test]
+fn replaces_stale_entries_first() {
+    let tt = TranspositionTable::new(1);
+    // All of these hashes map to the same bucket
+    let hashes: Vec<u64> = (1..=5).map(|i| i << 40).collect();
+
+    tt.store(hashes[0], 0, 20, Bound::Exact, 0);
+    tt.new_search();
+    for hash in &hashes[1..4] {
+        tt.store(*hash, 0, 1, Bound::Exact, 0);
+    }
+
+    // The bucket is full, so the deep entry from the previous search is the one to go
+    tt.store(hashes[4], 0, 1, Bound::Exact, 0);
+    assert!(tt.probe(hashes[0]).is_none());
+    for hash in &hashes[1..] {
+        assert!(tt.probe(*hash).is_some());
+    }
+}
+
+#[test]
+fn tablebase_depth_entries_can_be_overwritten() {
+    // Tablebase hits are stored with the greatest depth there is, so comparing depths mustn't overflow when they're stored again
+    let tt = TranspositionTable::new(1);
+    tt.store(42, 10, usize::MAX, Bound::Exact, 0);
+    tt.store(42, 20, usize::MAX, Bound::Exact, 0);
+
+    let entry = tt.probe(42).unwrap();
+    assert_eq!(entry.score, 20);
+    assert_eq!(entry.depth, u8::MAX as usize);
+}
+
+#[test]
+fn clear() {
+    let tt = TranspositionTable::new(1);
+
+    for hash in 0..1000u64 {
+        tt.store(hash, 0, 1, Bound::Exact, 0);
+    }
+    assert!(tt.hashfull() > 0);
+
+    tt.clear();
+    assert_eq!(tt.hashfull(), 0);
+    assert!(tt.probe(500).is_none());
+}
\ No newline at end of file
@@ -0,0 +1,94 @@
+use crate::state::State;
+use crate::tuner::{parse_position, evaluate_all, error, fit_k, local_search, Position};
+use crate::default_params::PARAMS;
+use crate::eval::classical_eval_with;
+
+#[test]
+fn parses_labelled_positions() {
+    let position = parse_position("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 [0.5]").unwrap();
+    assert_eq!(position.state.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert_eq!(position.result, 0.5);
+
+    // EPD, without the move counters
+    let position = parse_position("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1-0\";").unwrap();
+    assert_eq!(position.state.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1");
+    assert_eq!(position.result, 1.0);
+
+    let position = parse_position("8/8/4k3/8/8/4K3/8/8 w - - 12 40 | 0-1").unwrap();
+    assert_eq!(position.state.to_fen(), "8/8/4k3/8/8/4K3/8/8 w - - 12 40");
+    assert_eq!(position.result, 0.0);
+
+    assert!(parse_position("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").is_none());
+    assert!(parse_position("not a position [1.0]").is_none());
+}
+
+#[test]
+fn fits_k() {
+    // Results made up from the evaluations with a known K should give that K back
+    let fens = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/ppp1pppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR b KQkq - 0 1"
+    ];
+    let k = 1.5;
+    let mut positions: Vec<Position> = fens.iter().map(|fen| Position {
+        state: State::from_fen(fen).unwrap(),
+        result: 0.0
+    }).collect();
+    let evals = evaluate_all(&positions, &PARAMS, 2);
+    for (position, &eval) in positions.iter_mut().zip(evals.iter()) {
+        position.result = 1.0 / (1.0 + 10f64.powf(-k * eval as f64 / 400.0));
+    }
+
+    assert!((fit_k(&positions, &evals) - k).abs() < 0.01);
+}
+
+#[test]
+fn local_search_reduces_error() {
+    let positions: Vec<Position> = [
+        ("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", 0.5),
+        ("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", 1.0),
+        ("8/5k2/8/8/3K4/8/5P2/8 w - - 0 1", 1.0),
+        ("6k1/5ppp/8/8/8/8/r4PPP/6K1 w - - 0 1", 0.0)
+    ].iter().map(|&(fen, result)| Position {
+        state: State::from_fen(fen).unwrap(),
+        result
+    }).collect();
+    let k = 1.0;
+    let original_error = error(&positions, &evaluate_all(&positions, &PARAMS, 1), k);
+
+    let mut iterations = 0;
+    let params = local_search(&positions, PARAMS.clone(), k, 1, Some(1), |_, _, _| iterations += 1);
+    assert_eq!(iterations, 1);
+    assert!(error(&positions, &evaluate_all(&positions, &params, 1), k) < original_error);
+}
+
+#[test]
+fn default_params_match_tuner_output() {
+    // The checked in parameters must be exactly what the tuner would write for them, so that its output can replace the file as it is
+    assert_eq!(PARAMS.to_string(), include_str!("../default_params.rs"));
+}
+
+#[test]
+fn skips_parameters_which_cant_affect_the_eval() {
+    let tunable = PARAMS.tunable();
+    assert_eq!(tunable.len(), PARAMS.clone().values_mut().len());
+    assert!(tunable.iter().filter(|&&tunable| !tunable).count() > 0);
+
+    // Nudging any of the skipped values must leave every evaluation as it was
+    let states: Vec<State> = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        "8/1P3k2/8/2p5/3K4/8/5P2/8 w - - 0 1",
+        "6k1/1p3ppp/8/8/8/8/r4PPP/6K1 b - - 0 1"
+    ].iter().map(|fen| State::from_fen(fen).unwrap()).collect();
+    for i in (0..tunable.len()).filter(|&i| !tunable[i]) {
+        let mut params = PARAMS.clone();
+        *params.values_mut()[i] += 50;
+        for state in &states {
+            assert_eq!(classical_eval_with(state, &params), classical_eval_with(state, &PARAMS));
+        }
+    }
+}
\ No newline at end of file
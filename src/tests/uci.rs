@@ -1,5 +1,6 @@
 use crate::uci::UciHandler;
 use crate::state::State;
+use crate::tests::nnue::test_network_bytes;
 use regex::Regex;
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
@@ -145,11 +146,324 @@ fn go_with_depth() {
     sleep(Duration::from_secs(5));
 
     let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
-    assert!(output_str.contains("depth 4"));
-    assert!(!output_str.contains("depth 5"));
+    assert!(output_str.contains("info depth 4 "));
+    assert!(!output_str.contains("info depth 5 "));
     assert!(output_str.contains("bestmove h5g5"));
 }
 
+#[test]
+fn go_with_increment() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go wtime 2000 btime 2000 winc 100 binc 100 movestogo 10");
+
+    sleep(Duration::from_secs(2));
+
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains("bestmove h5g5"));
+}
+
+#[test]
+fn go_with_little_time_left() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen r1n4k/P1rq1pb1/1Qp1p2p/3pP1p1/3P4/5NP1/R2B1P1P/R5K1 w - - 6 32");
+    uci.command("go wtime 200 btime 200");
+
+    sleep(Duration::from_millis(200));
+
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains("bestmove"));
+}
+
+#[test]
+fn go_with_nodes() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // The limit is on the nodes searched by every thread together, not on each of them
+    uci.command("setoption name Threads value 2");
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go nodes 10000");
+
+    sleep(Duration::from_secs(5));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.contains("bestmove"));
+    let nodes: Vec<usize> = output_str.lines().filter_map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        tokens.iter().position(|&token| token == "nodes").map(|i| tokens[i + 1].parse().unwrap())
+    }).collect();
+    assert!(!nodes.is_empty());
+    assert!(nodes.iter().all(|&count| count < 12500));
+}
+
+#[test]
+fn go_with_mate() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen r1bq1rk1/pp1nbppp/2n1p3/3pP2Q/2pP4/2P4N/PPBN1PPP/R1B1K2R w KQ - 6 10");
+    uci.command("go mate 1");
+
+    sleep(Duration::from_secs(2));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.contains("bestmove h5h7"));
+    assert!(!output_str.contains("info depth 3 "));
+}
+
+#[test]
+fn go_infinite_waits_for_stop() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // Even though mate is found almost immediately, an infinite search must not report a bestmove until told to stop
+    uci.command("position fen r1bq1rk1/pp1nbppp/2n1p3/3pP2Q/2pP4/2P4N/PPBN1PPP/R1B1K2R w KQ - 6 10");
+    uci.command("go infinite");
+
+    sleep(Duration::from_secs(1));
+    assert!(!String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().any(|line| line.starts_with("bestmove")));
+
+    uci.command("stop");
+    sleep(Duration::from_secs(1));
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains("bestmove h5h7"));
+}
+
+#[test]
+fn go_infinite_with_own_book_waits_for_stop() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // The starting position is in the book, but an infinite search still has to wait for stop rather than play the book move at once
+    uci.command("setoption name OwnBook value true");
+    uci.command("position startpos");
+    uci.command("go infinite");
+
+    sleep(Duration::from_secs(1));
+    assert!(!String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().any(|line| line.starts_with("bestmove")));
+
+    uci.command("stop");
+    sleep(Duration::from_secs(1));
+    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().filter(|line| line.starts_with("bestmove")).count(), 1);
+}
+
+#[test]
+fn go_with_threads_stops_cleanly() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("setoption name Threads value 4");
+    uci.command("position fen r1n4k/P1rq1pb1/1Qp1p2p/3pP1p1/3P4/5NP1/R2B1P1P/R5K1 w - - 6 32");
+    uci.command("go infinite");
+
+    sleep(Duration::from_secs(1));
+    uci.command("stop");
+    sleep(Duration::from_secs(1));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert_eq!(output_str.lines().filter(|line| line.starts_with("bestmove")).count(), 1);
+}
+
+#[test]
+fn go_with_depth_finds_mate_at_full_depth() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // Mate in 3 takes five plies to see, so the root mustn't be searched any shallower than the depth it reports
+    uci.command("position fen r5k1/2p2ppp/2q5/7b/2r5/4R1PP/2P1QP2/4R1K1 w - - 0 32");
+    uci.command("go depth 5");
+
+    sleep(Duration::from_secs(5));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.lines().any(|line| line.starts_with("info depth 5 ") && line.contains(" score mate 3 ")));
+    assert!(!output_str.contains("info depth 6 "));
+    assert!(output_str.contains("bestmove e3e8"));
+}
+
+#[test]
+fn go_with_searchmoves() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // Capturing the queen is by far the best move, but we've excluded it
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go depth 3 searchmoves a2a3 b2b3");
+
+    sleep(Duration::from_secs(2));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.contains("bestmove a2a3") || output_str.contains("bestmove b2b3"));
+}
+
+#[test]
+fn go_with_searchmoves_in_book_position() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // a2a3 isn't a book move, so the book mustn't be allowed to answer for it
+    uci.command("setoption name OwnBook value true");
+    uci.command("position startpos");
+    uci.command("go depth 3 searchmoves a2a3");
+
+    sleep(Duration::from_secs(2));
+
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains("bestmove a2a3"));
+}
+
+#[test]
+fn aspiration_window_widens() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // The mate only turns up at depth 5, so the score jumps far above the window set from depth 4 and it has to widen until it's opened right up
+    uci.command("position fen r5k1/2p2ppp/2q5/7b/2r5/4R1PP/2P1QP2/4R1K1 w - - 0 32");
+    uci.command("go depth 6");
+
+    sleep(Duration::from_secs(3));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.lines().any(|line| line.starts_with("info depth 4 ") && line.contains(" upperbound ")));
+
+    let fail_highs: Vec<isize> = output_str.lines().filter(|line| line.starts_with("info depth 5 ") && line.contains(" lowerbound ")).map(|line| {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let i = tokens.iter().position(|&token| token == "cp").unwrap();
+        tokens[i + 1].parse().unwrap()
+    }).collect();
+    assert!(fail_highs.len() >= 3);
+    // Each time the window is widened by more than the last
+    for scores in fail_highs.windows(3) {
+        assert!(scores[2] - scores[1] > scores[1] - scores[0]);
+    }
+
+    assert!(output_str.lines().any(|line| line.starts_with("info depth 5 ") && line.contains(" score mate 3 nodes ")));
+    assert!(output_str.contains("bestmove e3e8"));
+}
+
+#[test]
+fn go_with_missing_values() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go movetime 500 wtime");
+
+    sleep(Duration::from_secs(2));
+
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains("bestmove h5g5"));
+}
+
+#[test]
+fn info_lines() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go depth 3");
+
+    sleep(Duration::from_secs(2));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    let info = Regex::new(r"(?m)^info depth 3 seldepth \d+ multipv 1 score cp -?\d+ nodes \d+ nps \d+ time \d+ hashfull \d+ tbhits \d+ pv h5g5 .*$").unwrap();
+    assert!(info.is_match(&output_str));
+    assert!(output_str.contains("\ninfo string tthits "));
+    // The bestmove must come after all of the info lines
+    assert!(output_str.lines().last().unwrap().starts_with("bestmove h5g5"));
+}
+
+#[test]
+fn info_lines_with_mate_score() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen r1bq1rk1/pp1nbppp/2n1p3/3pP2Q/2pP4/2P4N/PPBN1PPP/R1B1K2R w KQ - 6 10");
+    uci.command("go depth 2");
+
+    sleep(Duration::from_secs(2));
+
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains(" score mate 1 "));
+}
+
+#[test]
+fn go_with_multi_pv() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("setoption name MultiPV value 3");
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go depth 3");
+
+    sleep(Duration::from_secs(2));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(Regex::new(r"(?m)^info depth 3 seldepth \d+ multipv 1 .* pv h5g5").unwrap().is_match(&output_str));
+    assert!(Regex::new(r"(?m)^info depth 3 seldepth \d+ multipv 2 ").unwrap().is_match(&output_str));
+    assert!(Regex::new(r"(?m)^info depth 3 seldepth \d+ multipv 3 ").unwrap().is_match(&output_str));
+    assert!(!output_str.contains("multipv 4"));
+    assert!(output_str.contains("bestmove h5g5"));
+}
+
+#[test]
+fn bestmove_with_ponder_move() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go depth 4");
+
+    sleep(Duration::from_secs(2));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(Regex::new(r"(?m)^bestmove h5g5 ponder [a-h][1-8][a-h][1-8]$").unwrap().is_match(&output_str));
+}
+
+#[test]
+fn go_ponder_then_ponderhit() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go ponder movetime 500");
+
+    // While pondering there's no time limit, so we mustn't move until the opponent does
+    sleep(Duration::from_secs(2));
+    assert!(!String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().any(|line| line.starts_with("bestmove")));
+
+    // From here on we're on our own clock, and so should move within the given movetime
+    uci.command("ponderhit");
+    sleep(Duration::from_secs(2));
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().contains("bestmove h5g5"));
+
+    // The same goes for a position in the book, which mustn't be answered with a book move before the ponderhit
+    uci.command("setoption name OwnBook value true");
+    uci.command("position startpos");
+    uci.command("go ponder movetime 500");
+
+    sleep(Duration::from_secs(1));
+    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().filter(|line| line.starts_with("bestmove")).count(), 1);
+
+    uci.command("ponderhit");
+    sleep(Duration::from_secs(2));
+    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().filter(|line| line.starts_with("bestmove")).count(), 2);
+}
+
+#[test]
+fn go_ponder_then_stop() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("position fen rnb1kbnr/pppp1ppp/8/4p1qQ/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 2 3");
+    uci.command("go ponder wtime 10000 btime 10000");
+
+    sleep(Duration::from_secs(1));
+    uci.command("stop");
+    sleep(Duration::from_secs(1));
+
+    assert!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap().lines().any(|line| line.starts_with("bestmove")));
+}
+
 #[test]
 fn uci() {
     let output = Arc::new(Mutex::new(Vec::new()));
@@ -157,7 +471,35 @@ fn uci() {
 
     uci.command("uci");
 
-    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap(), "id name silverfish\nuciok\n");
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.starts_with("id name silverfish\n"));
+    assert!(output_str.contains("option name Hash type spin default 16 min 1 max 65536\n"));
+    assert!(output_str.contains("option name Clear Hash type button\n"));
+    assert!(output_str.contains("option name SyzygyPath type string default <empty>\n"));
+    assert!(output_str.contains("option name Ponder type check default false\n"));
+    assert!(output_str.contains("option name Move Overhead type spin default 10 min 0 max 5000\n"));
+    assert!(output_str.contains("option name EvalFile type string default <empty>\n"));
+    assert!(output_str.contains("option name UseNNUE type check default true\n"));
+    assert!(output_str.contains("option name RFPMargin type spin default 80 min 0 max 1000\n"));
+    assert!(output_str.contains("option name FutilityMargin type spin default 80 min 0 max 1000\n"));
+    assert!(output_str.ends_with("uciok\n"));
+}
+
+#[test]
+fn setoption_own_book() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    // From the starting position the book would otherwise answer instantly without reporting any search info
+    uci.command("setoption name OwnBook value false");
+    uci.command("position startpos");
+    uci.command("go depth 2");
+
+    sleep(Duration::from_secs(2));
+
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    assert!(output_str.contains("depth 2"));
+    assert!(output_str.contains("bestmove"));
 }
 
 #[test]
@@ -179,7 +521,52 @@ fn eval() {
 
     uci.command("eval");
 
-    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap(), "0\n");
+    // The starting position is symmetrical, leaving just the bonus for having the move
+    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap(), "10\n");
+}
+
+#[test]
+fn eval_with_network() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    let path = std::env::temp_dir().join("silverfish_eval_with_network.bin");
+    std::fs::write(&path, test_network_bytes()).unwrap();
+    uci.command(&format!("setoption name EvalFile value {}", path.display()));
+    uci.command("position startpos moves e2e4");
+    uci.command("eval");
+    uci.command("setoption name UseNNUE value false");
+    uci.command("eval");
+    std::fs::remove_file(&path).unwrap();
+
+    // The network's evaluation differs from the classical one, which comes back once the network is turned off
+    let classical = State::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+    let output_str = String::from_utf8(output.lock().unwrap().to_vec()).unwrap();
+    let lines: Vec<&str> = output_str.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_ne!(lines[0], lines[1]);
+    assert_eq!(lines[1], crate::eval::eval(&classical).to_string());
+}
+
+#[test]
+fn eval_file_which_cannot_be_loaded() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("setoption name EvalFile value /nonexistent/network.bin");
+    uci.command("eval");
+
+    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap(), "info string failed to load network /nonexistent/network.bin\n10\n");
+}
+
+#[test]
+fn syzygy_path_which_cannot_be_loaded() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut uci = UciHandler::new(None, output.clone());
+
+    uci.command("setoption name SyzygyPath value /nonexistent/syzygy");
+
+    assert_eq!(String::from_utf8(output.lock().unwrap().to_vec()).unwrap(), "info string failed to load tablebases /nonexistent/syzygy\n");
 }
 
 #[test]
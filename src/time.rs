@@ -0,0 +1,95 @@
+use crate::colours::Colour;
+use crate::limits::SearchLimits;
+use crate::moves::BitMove;
+use std::cmp::{max, min};
+use std::time::{Duration, Instant};
+
+// Without movestogo we have no idea how long the game will go on for, so we budget as if this many moves are left
+const DEFAULT_MOVES_TO_GO: u32 = 25;
+const MAX_MOVES_TO_GO: u32 = 50;
+// However the search is going, we never let a single move take more than this multiple of its share of the time
+const HARD_LIMIT_MULTIPLIER: u32 = 4;
+// The soft limit is scaled down as the best move stays the same across iterations, from the first entry towards the last
+const STABILITY_SCALES: [f64; 7] = [1.5, 1.25, 1.0, 0.85, 0.75, 0.65, 0.55];
+// A score which falls this far since the last iteration gets the most extra time
+const SCORE_DROP_LIMIT: isize = 100;
+
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    previous: Option<(BitMove, isize)>,
+    stability: usize,
+    scale: f64
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, colour: Colour, move_overhead: Duration) -> Self {
+        let mut time_manager = Self::unlimited();
+        if limits.infinite {
+            return time_manager;
+        }
+
+        if let Some(movetime) = limits.movetime {
+            // A fixed time per move leaves nothing to manage, so it's just a hard limit
+            time_manager.hard_limit = Some(movetime.saturating_sub(move_overhead));
+        }
+        else if let Some(remaining) = limits.times[colour as usize] {
+            // Leave some slack for the time it takes for our move to reach the GUI
+            let remaining = remaining.saturating_sub(move_overhead);
+            let increment = limits.increments[colour as usize].unwrap_or_default();
+            let moves_to_go = limits.movestogo.map_or(DEFAULT_MOVES_TO_GO, |n| n as u32).clamp(1, MAX_MOVES_TO_GO);
+
+            // Whatever happens we keep back a share of the clock, so that there's always time left for the rest of the moves
+            let soft_limit = min(remaining / moves_to_go + increment * 3 / 4, remaining / 2);
+            let hard_limit = min(soft_limit * HARD_LIMIT_MULTIPLIER, remaining * 3 / 4);
+
+            time_manager.soft_limit = Some(soft_limit);
+            time_manager.hard_limit = Some(max(hard_limit, soft_limit));
+        }
+
+        time_manager
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            start: Instant::now(),
+            soft_limit: None,
+            hard_limit: None,
+            previous: None,
+            stability: 0,
+            scale: 1.0
+        }
+    }
+
+    // Should be checked regularly during the search, which has to stop straight away once this is reached
+    pub fn hard_limit_reached(&self) -> bool {
+        matches!(self.hard_limit, Some(limit) if self.start.elapsed() >= limit)
+    }
+
+    // Should be checked between iterations, since starting another one after this is reached would most likely be a waste of time
+    pub fn soft_limit_reached(&self) -> bool {
+        matches!(self.soft_limit, Some(limit) if self.start.elapsed().as_secs_f64() >= limit.as_secs_f64() * self.scale)
+    }
+
+    // Takes the result of each completed iteration, to spend more time when the search is unsettled and less when it isn't
+    pub fn update(&mut self, best_move: BitMove, score: isize) {
+        let mut score_scale = 1.0;
+        if let Some((previous_move, previous_score)) = self.previous {
+            self.stability = if best_move == previous_move { min(self.stability + 1, STABILITY_SCALES.len() - 1) } else { 0 };
+            if score < previous_score {
+                score_scale += min(previous_score - score, SCORE_DROP_LIMIT) as f64 / (2 * SCORE_DROP_LIMIT) as f64;
+            }
+        }
+
+        self.previous = Some((best_move, score));
+        self.scale = STABILITY_SCALES[self.stability] * score_scale;
+    }
+
+    // With only one legal move, there's nothing to think about
+    pub fn forced_move(&mut self) {
+        if self.soft_limit.is_some() {
+            self.soft_limit = Some(Duration::ZERO);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,171 @@
+use crate::moves::BitMove;
+use crate::search::Bound;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// Four 16 byte entries, so that a whole bucket fits in a single cache line
+const BUCKET_SIZE: usize = 4;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TtEntry {
+    pub score: isize,
+    pub depth: usize,
+    pub bound: Bound,
+    pub r#move: BitMove,
+    age: u8
+}
+
+impl TtEntry {
+    // Packed as: move (bits 0-21), score (22-37), depth (38-45), bound (46-47), age (48-55)
+    fn encode(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3
+        };
+
+        (self.r#move as u64 & 0x3fffff)
+            | ((self.score as i16 as u16 as u64) << 22)
+            | ((self.depth.min(u8::MAX as usize) as u64) << 38)
+            | (bound << 46)
+            | ((self.age as u64) << 48)
+    }
+
+    fn decode(data: u64) -> Option<Self> {
+        let bound = match (data >> 46) & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => {
+                return None;
+            }
+        };
+
+        Some(Self {
+            score: ((data >> 22) & 0xffff) as u16 as i16 as isize,
+            depth: ((data >> 38) & 0xff) as usize,
+            bound,
+            r#move: (data & 0x3fffff) as BitMove,
+            age: ((data >> 48) & 0xff) as u8
+        })
+    }
+}
+
+// The key is stored XORed with the data, so that an entry torn by two threads writing to it at once fails to match on probing
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64
+}
+
+impl Slot {
+    fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        (self.key.load(Ordering::Relaxed) ^ data, data)
+    }
+
+    fn store(&self, hash: u64, data: u64) {
+        self.key.store(hash ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct Bucket {
+    slots: [Slot; BUCKET_SIZE]
+}
+
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    age: AtomicU8
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let max_buckets = (size_mb.max(1) * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+        // Round down to a power of two so that the index can be taken by masking rather than with a modulo
+        let num_buckets = 1 << max_buckets.ilog2();
+
+        Self {
+            buckets: (0..num_buckets).map(|_| Bucket::default()).collect(),
+            age: AtomicU8::new(0)
+        }
+    }
+
+    fn bucket(&self, hash: u64) -> &Bucket {
+        &self.buckets[hash as usize & (self.buckets.len() - 1)]
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        for slot in &self.bucket(hash).slots {
+            let (key, data) = slot.load();
+            if key == hash {
+                return TtEntry::decode(data);
+            }
+        }
+
+        None
+    }
+
+    pub fn store(&self, hash: u64, score: isize, depth: usize, bound: Bound, r#move: BitMove) {
+        let age = self.age.load(Ordering::Relaxed);
+        let bucket = self.bucket(hash);
+
+        let mut replace = 0;
+        let mut replace_worth = isize::MAX;
+        for (i, slot) in bucket.slots.iter().enumerate() {
+            let (key, data) = slot.load();
+            let existing = TtEntry::decode(data);
+
+            if key == hash {
+                if let Some(existing) = existing {
+                    // Don't let a shallow result from the current search clobber a deeper one, not even an exact one from quiescence
+                    if existing.age == age && depth.saturating_add(2) < existing.depth {
+                        return;
+                    }
+                    // Keep hold of the old move for ordering if we don't have a better one
+                    let r#move = if r#move == 0 { existing.r#move } else { r#move };
+                    slot.store(hash, TtEntry { score, depth, bound, r#move, age }.encode());
+                    return;
+                }
+            }
+
+            // Prefer to replace empty slots, then ones left over from previous searches, then the shallowest
+            let worth = match existing {
+                Some(existing) if existing.age != age => existing.depth as isize - 256,
+                Some(existing) => existing.depth as isize,
+                None => isize::MIN
+            };
+            if worth < replace_worth {
+                replace = i;
+                replace_worth = worth;
+            }
+        }
+
+        bucket.slots[replace].store(hash, TtEntry { score, depth, bound, r#move, age }.encode());
+    }
+
+    // Should be called at the start of each search, so that entries from previous searches can be told apart and replaced first
+    pub fn new_search(&self) {
+        self.age.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            for slot in &bucket.slots {
+                slot.store(0, 0);
+            }
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
+
+    // An estimate of how full the table is in permille, based on the first thousand or so entries
+    pub fn hashfull(&self) -> usize {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = &self.buckets[..(1000 / BUCKET_SIZE).min(self.buckets.len())];
+        let used = sample.iter().flat_map(|bucket| bucket.slots.iter()).filter(|slot| {
+            matches!(TtEntry::decode(slot.load().1), Some(entry) if entry.age == age)
+        }).count();
+
+        used * 1000 / (sample.len() * BUCKET_SIZE)
+    }
+}
\ No newline at end of file
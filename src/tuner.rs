@@ -0,0 +1,154 @@
+use crate::state::State;
+use crate::eval::classical_eval_with;
+use crate::params::EvalParams;
+use crate::default_params::PARAMS;
+use std::fs;
+use std::io;
+use std::thread;
+
+// The sigmoid's scaling constant is searched for in this range
+const K_MIN: f64 = 0.0;
+const K_MAX: f64 = 10.0;
+const K_ITERATIONS: usize = 100;
+
+// A position, parsed once up front rather than on every evaluation, with the game's result for White
+pub struct Position {
+    pub state: State,
+    pub result: f64
+}
+
+// Takes lines such as `<fen> [0.5]`, `<fen> c9 "1-0";` or `<fen> | 1/2-1/2`, with or without the halfmove and fullmove counters
+pub fn parse_position(line: &str) -> Option<Position> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 5 {
+        return None;
+    }
+
+    let result_index = (4..tokens.len()).rev().find(|&i| parse_result(tokens[i]).is_some())?;
+    let result = parse_result(tokens[result_index])?;
+
+    let mut fen = tokens[..4].join(" ");
+    if result_index > 5 && tokens[4].parse::<u32>().is_ok() && tokens[5].parse::<u32>().is_ok() {
+        fen = format!("{} {} {}", fen, tokens[4], tokens[5]);
+    }
+    else {
+        fen.push_str(" 0 1");
+    }
+    Some(Position {
+        state: State::from_fen(&fen).ok()?,
+        result
+    })
+}
+
+fn parse_result(token: &str) -> Option<f64> {
+    match token.trim_matches(|c| c == '[' || c == ']' || c == '"' || c == ';') {
+        "1-0" | "1.0" => Some(1.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        "0-1" | "0.0" => Some(0.0),
+        _ => None
+    }
+}
+
+fn sigmoid(score: isize, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0))
+}
+
+// Every position's evaluation from White's point of view, split across threads
+pub fn evaluate_all(positions: &[Position], params: &EvalParams, threads: usize) -> Vec<isize> {
+    let chunk_size = positions.len().div_ceil(threads.max(1)).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = positions.chunks(chunk_size).map(|chunk| scope.spawn(move || {
+            chunk.iter().map(|position| classical_eval_with(&position.state, params)).collect::<Vec<isize>>()
+        })).collect();
+
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    })
+}
+
+// The mean squared difference between the results and the results the evaluations predict
+pub fn error(positions: &[Position], evals: &[isize], k: f64) -> f64 {
+    let total: f64 = positions.iter().zip(evals.iter()).map(|(position, &eval)| (position.result - sigmoid(eval, k)).powi(2)).sum();
+    total / positions.len() as f64
+}
+
+// Finds the scaling constant which best maps the evaluations onto the results, by ternary search since the error has a single minimum
+pub fn fit_k(positions: &[Position], evals: &[isize]) -> f64 {
+    let (mut low, mut high) = (K_MIN, K_MAX);
+    for _ in 0..K_ITERATIONS {
+        let third = (high - low) / 3.0;
+        if error(positions, evals, low + third) < error(positions, evals, high - third) {
+            high -= third;
+        }
+        else {
+            low += third;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+// Phases have to stay positive for the midgame and endgame scores to be blended sensibly
+fn is_valid(params: &EvalParams) -> bool {
+    params.phase_increments.iter().all(|&increment| increment >= 0) && params.max_phase() > 0
+}
+
+// Nudges each parameter which can affect the evaluation up or down by one for as long as that reduces the error, calling back with the parameters after each pass
+pub fn local_search<F: FnMut(usize, f64, &EvalParams)>(positions: &[Position], mut params: EvalParams, k: f64, threads: usize, max_iterations: Option<usize>, mut on_iteration: F) -> EvalParams {
+    let tunable = params.tunable();
+    let mut best_error = error(positions, &evaluate_all(positions, &params, threads), k);
+
+    let mut iteration = 0;
+    let mut improved = true;
+    while improved && max_iterations.is_none_or(|max| iteration < max) {
+        improved = false;
+        iteration += 1;
+
+        for i in (0..tunable.len()).filter(|&i| tunable[i]) {
+            for step in &[1, -1] {
+                let mut candidate = params.clone();
+                *candidate.values_mut()[i] += step;
+                if !is_valid(&candidate) {
+                    continue;
+                }
+
+                let candidate_error = error(positions, &evaluate_all(positions, &candidate, threads), k);
+                if candidate_error < best_error {
+                    params = candidate;
+                    best_error = candidate_error;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+
+        on_iteration(iteration, best_error, &params);
+    }
+
+    params
+}
+
+// Tunes the engine's evaluation parameters on a file of positions, writing the best found so far to the output after each pass
+pub fn tune(path: &str, output: &str, threads: usize) -> io::Result<()> {
+    let data = fs::read_to_string(path)?;
+    let positions: Vec<Position> = data.lines().filter_map(parse_position).collect();
+    let skipped = data.lines().filter(|line| !line.trim().is_empty()).count() - positions.len();
+    println!("Loaded {} positions, skipping {} lines which couldn't be read", positions.len(), skipped);
+    if positions.is_empty() {
+        return Ok(());
+    }
+
+    let k = fit_k(&positions, &evaluate_all(&positions, &PARAMS, threads));
+    println!("K = {:.4}", k);
+
+    let mut write_result = Ok(());
+    local_search(&positions, PARAMS.clone(), k, threads, None, |iteration, error, params| {
+        println!("Iteration {}: error {:.6}", iteration, error);
+        if write_result.is_ok() {
+            write_result = fs::write(output, params.to_string());
+        }
+    });
+    write_result?;
+
+    println!("Tuned parameters written to {}", output);
+    Ok(())
+}
\ No newline at end of file
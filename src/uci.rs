@@ -1,37 +1,63 @@
 use crate::state::State;
 use crate::helpers::{algebraic_to_sq};
-use crate::moves::{move_from, move_to, move_promotion_piece, generate_moves, move_to_algebraic, move_string_is_valid};
+use crate::moves::{BitMove, move_from, move_to, move_promotion_piece, generate_moves, move_to_algebraic, move_string_is_valid};
 use crate::colours::Colour;
 use crate::pieces::Piece;
 use crate::perft::perft;
 use crate::eval::eval;
-use crate::search::{Search, Message};
+use crate::search::{Search, Message, Bound, PruningMargins, mate_in};
+use crate::options::Options;
+use crate::limits::SearchLimits;
+use crate::tt::TranspositionTable;
+use crate::ordering::MoveHistory;
+use crate::nnue::Network;
+use std::cmp::max;
 use std::process::exit;
 use std::time::Duration;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{channel, Sender};
+use shakmaty::Chess;
+use shakmaty_syzygy::Tablebase;
 
 pub struct UciHandler {
     state: State,
-    tb_directory: Option<String>,
+    options: Options,
+    tablebase: Option<Arc<Tablebase<Chess>>>,
+    tt: Arc<TranspositionTable>,
+    move_histories: Arc<Mutex<Vec<MoveHistory>>>,
+    network: Option<Arc<Network>>,
     out: Arc<Mutex<dyn std::io::Write + std::marker::Send>>,
     transmitter: Option<Sender<Message>>
 }
 
 impl UciHandler {
     pub fn new(tb_directory: Option<String>, out: Arc<Mutex<dyn std::io::Write + std::marker::Send>>) -> Self {
-        Self {
+        let options = Options::new();
+        let mut handler = Self {
             state: State::start_pos(),
-            tb_directory: tb_directory,
-            out: out,
+            tt: Arc::new(TranspositionTable::new(options.spin("Hash") as usize)),
+            move_histories: Arc::new(Mutex::new(Vec::new())),
+            options,
+            tablebase: None,
+            network: None,
+            out,
             transmitter: None
+        };
+
+        if let Some(dir) = tb_directory {
+            handler.setoption(&format!("setoption name SyzygyPath value {}", dir));
         }
+
+        handler
     }
 
     pub fn command(&mut self, command: &str) {
         if command.starts_with("isready") {
             self.isready();
         }
+        else if command.starts_with("setoption") {
+            self.setoption(command);
+        }
         else if command.starts_with("ucinewgame") {
             self.ucinewgame();
         }
@@ -44,6 +70,9 @@ impl UciHandler {
         else if command.starts_with("stop") {
             self.stop();
         }
+        else if command.starts_with("ponderhit") {
+            self.ponderhit();
+        }
         else if command.starts_with("uci") {
             self.uci();
         }
@@ -65,8 +94,66 @@ impl UciHandler {
         writeln!(self.out.lock().unwrap(), "readyok").unwrap();
     }
 
+    fn setoption(&mut self, command: &str) {
+        // Both the option name and its value may contain spaces, e.g. "setoption name Move Overhead value 30"
+        let command = command.trim();
+        let name_start = match command.find(" name ") {
+            Some(i) => i + " name ".len(),
+            None => {
+                return;
+            }
+        };
+        let (name, value) = match command[name_start..].find(" value ") {
+            Some(i) => (&command[name_start..name_start+i], Some(command[name_start+i+" value ".len()..].trim())),
+            None => (&command[name_start..], None)
+        };
+
+        match self.options.set(name.trim(), value) {
+            Ok("SyzygyPath") => {
+                // As with EvalFile, a directory which can't be loaded leaves us without tablebases
+                self.tablebase = match self.options.string("SyzygyPath") {
+                    Some(dir) => {
+                        let mut tb = Tablebase::new();
+                        match tb.add_directory(&dir) {
+                            Ok(_) => Some(Arc::new(tb)),
+                            Err(_) => {
+                                writeln!(self.out.lock().unwrap(), "info string failed to load tablebases {}", dir).unwrap();
+                                None
+                            }
+                        }
+                    },
+                    None => None
+                };
+            },
+            Ok("Hash") => {
+                // Any search still running keeps hold of the old table until it finishes
+                self.tt = Arc::new(TranspositionTable::new(self.options.spin("Hash") as usize));
+            },
+            Ok("Clear Hash") => {
+                self.tt.clear();
+            },
+            Ok("EvalFile") => {
+                // Any search still running keeps hold of the old network until it finishes.
+                // A file which can't be loaded leaves us with the classical evaluation
+                self.network = match self.options.string("EvalFile") {
+                    Some(path) => match Network::from_file(&path) {
+                        Ok(network) => Some(Arc::new(network)),
+                        Err(_) => {
+                            writeln!(self.out.lock().unwrap(), "info string failed to load network {}", path).unwrap();
+                            None
+                        }
+                    },
+                    None => None
+                };
+            },
+            _ => {}
+        }
+    }
+
     fn ucinewgame(&mut self,) {
         self.state = State::start_pos();
+        self.tt.clear();
+        self.move_histories.lock().unwrap().clear();
     }
 
     fn position(&mut self, command: &str) {
@@ -97,70 +184,85 @@ impl UciHandler {
         };
     
         if let Some("moves") = segments.next() {
-            'moveparsing: loop {
-                match segments.next() {
-                    Some(move_string) if move_string_is_valid(move_string) => {
-                        let mut move_list = generate_moves(&state);
-    
-                        let from = algebraic_to_sq(&move_string[0..2]);
-                        let to = algebraic_to_sq(&move_string[2..4]);
-                        let promotion_piece = if move_string.len() == 5 {
-                            match &move_string[4..5] {
-                                "n" => Some(Piece::Knight),
-                                "b" => Some(Piece::Bishop),
-                                "r" => Some(Piece::Rook),
-                                "q" => Some(Piece::Queen),
-                                _ => {
-                                    return;
-                                }
-                            }
-                        } else { None };
-    
-                        while let Some(r#move) = move_list.next() {
-                            if from == move_from(r#move) && to == move_to(r#move) && promotion_piece == move_promotion_piece(r#move) {
-                                // If this is a legal move, proceed to parse the next move. Otherwise, stop parsing the moves altogether
-                                match state.make_move(r#move) {
-                                    Ok(_) => {
-                                        break;
-                                    },
-                                    Err(_) => {
-                                        break 'moveparsing;
-                                    }
-                                }
-                            }
-                        }
-                    },
-                    _ => break
-                }
+            // If this is a legal move, proceed to parse the next move. Otherwise, stop parsing the moves altogether
+            while let Some(r#move) = segments.next().and_then(|s| parse_move(&state, s)) {
+                state.make_move(r#move).unwrap();
             }
         }
-        
+
         self.state = state;
     }
 
+    // The network to evaluate with, if one is loaded and enabled
+    fn network(&self) -> Option<Arc<Network>> {
+        self.network.clone().filter(|_| self.options.check("UseNNUE"))
+    }
+
     fn go(&mut self, command: &str) {
-        let mut searcher = Search::new(self.state, &self.tb_directory);
+        let mut searcher = Search::new(self.state, &self.tablebase);
+        searcher.set_own_book(self.options.check("OwnBook"));
+        self.tt.new_search();
+        searcher.set_transposition_table(self.tt.clone());
+        searcher.set_move_histories(self.move_histories.clone());
+        searcher.set_network(self.network());
+        searcher.set_multi_pv(self.options.spin("MultiPV") as usize);
+        searcher.set_pruning_margins(PruningMargins {
+            reverse_futility: self.options.spin("RFPMargin"),
+            razoring: self.options.spin("RazoringMargin"),
+            futility_base: self.options.spin("FutilityBaseMargin"),
+            futility: self.options.spin("FutilityMargin")
+        });
+        searcher.set_threads(self.options.spin("Threads") as usize);
+        searcher.set_move_overhead(Duration::from_millis(self.options.spin("Move Overhead") as u64));
 
-        let mut segments = command.split_whitespace().skip(1);
-        loop {
-            match segments.next() {
-                Some("depth") => {
-                    searcher.set_depth(segments.next().unwrap().parse().unwrap());
+        // Parameters with a missing or malformed value are ignored rather than aborting the whole command
+        let mut limits = SearchLimits::new();
+        let mut segments = command.split_whitespace().skip(1).peekable();
+        while let Some(segment) = segments.next() {
+            match segment {
+                "depth" => {
+                    limits.depth = segments.next().and_then(|s| s.parse().ok());
                 },
-                Some("movetime") => {
-                    searcher.set_search_duration(Some(Duration::from_millis(segments.next().unwrap().parse().unwrap())));
+                "nodes" => {
+                    limits.nodes = segments.next().and_then(|s| s.parse().ok());
                 },
-                Some("wtime") => {
-                    searcher.set_time(Colour::White, Some(Duration::from_millis(segments.next().unwrap().parse().unwrap())));
+                "mate" => {
+                    limits.mate = segments.next().and_then(|s| s.parse().ok());
                 },
-                Some("btime") => {
-                    searcher.set_time(Colour::Black, Some(Duration::from_millis(segments.next().unwrap().parse().unwrap())));
+                "movetime" => {
+                    limits.movetime = segments.next().and_then(parse_millis);
                 },
-                _ => {
-                    break;
-                }
+                "wtime" => {
+                    limits.times[Colour::White as usize] = segments.next().and_then(parse_millis);
+                },
+                "btime" => {
+                    limits.times[Colour::Black as usize] = segments.next().and_then(parse_millis);
+                },
+                "winc" => {
+                    limits.increments[Colour::White as usize] = segments.next().and_then(parse_millis);
+                },
+                "binc" => {
+                    limits.increments[Colour::Black as usize] = segments.next().and_then(parse_millis);
+                },
+                "movestogo" => {
+                    limits.movestogo = segments.next().and_then(|s| s.parse().ok());
+                },
+                "infinite" => {
+                    limits.infinite = true;
+                },
+                "ponder" => {
+                    limits.ponder = true;
+                },
+                "searchmoves" => {
+                    while let Some(r#move) = segments.peek().and_then(|s| parse_move(&self.state, s)) {
+                        limits.searchmoves.push(r#move);
+                        segments.next();
+                    }
+                },
+                _ => {}
             }
         }
+        searcher.set_limits(limits);
 
         let (uci_transmitter, search_receiver) = channel();
         let (search_transmitter, uci_receiver) = channel();
@@ -168,17 +270,29 @@ impl UciHandler {
         searcher.set_channels(Some((search_transmitter, search_receiver)));
 
         let out1 = self.out.clone();
-        std::thread::spawn(move || {
-            let bestmove = searcher.go();
-            writeln!(out1.lock().unwrap(), "bestmove {}", move_to_algebraic(bestmove.0)).unwrap();
-        });
-
-        let out2 = self.out.clone();
-        std::thread::spawn(move || {
+        let info_thread = std::thread::spawn(move || {
             loop {
                 match uci_receiver.recv().unwrap() {
-                    Message::Info(depth, nodes, tt_hits, tb_hits, duration, bestmove, cp, pv) => {
-                        writeln!(out2.lock().unwrap(), "info depth {} nodes {} millis {} nps {} tt_hits {} tb_hits {} bestmove {} cp {} pv {}", depth, nodes, duration.as_millis(), (1000000 * nodes as u128 / duration.as_micros()), tt_hits, tb_hits, move_to_algebraic(bestmove), cp, pv).unwrap()
+                    Message::Info(info) => {
+                        let mut out = out1.lock().unwrap();
+                        writeln!(out, "info depth {} seldepth {} multipv {} score {}{} nodes {} nps {} time {} hashfull {} tbhits {} pv {}",
+                            info.depth,
+                            info.seldepth,
+                            info.multipv,
+                            format_score(info.score),
+                            match info.bound {
+                                Bound::Exact => "",
+                                Bound::Lower => " lowerbound",
+                                Bound::Upper => " upperbound"
+                            },
+                            info.nodes,
+                            1000 * info.nodes as u128 / max(info.duration.as_millis(), 1),
+                            info.duration.as_millis(),
+                            info.hashfull,
+                            info.tb_hits,
+                            info.pv
+                        ).unwrap();
+                        writeln!(out, "info string tthits {}", info.tt_hits).unwrap();
                     },
                     Message::Done => {
                         break;
@@ -187,14 +301,37 @@ impl UciHandler {
                 }
             }
         });
+
+        let out2 = self.out.clone();
+        std::thread::spawn(move || {
+            let bestmove = searcher.go();
+            // Make sure every info line has been written before we announce the move
+            info_thread.join().unwrap();
+            match searcher.pv_lines().first().filter(|line| line.pv.length > 1) {
+                Some(line) => writeln!(out2.lock().unwrap(), "bestmove {} ponder {}", move_to_algebraic(bestmove.0), move_to_algebraic(line.pv.moves[1])).unwrap(),
+                None => writeln!(out2.lock().unwrap(), "bestmove {}", move_to_algebraic(bestmove.0)).unwrap()
+            }
+        });
     }
 
     fn stop(&mut self) {
-        self.transmitter.as_ref().unwrap().send(Message::Stop).unwrap();
+        // The search may well have finished already, in which case there's nobody left to receive this
+        if let Some(transmitter) = &self.transmitter {
+            let _ = transmitter.send(Message::Stop);
+        }
+    }
+
+    fn ponderhit(&mut self) {
+        if let Some(transmitter) = &self.transmitter {
+            let _ = transmitter.send(Message::PonderHit);
+        }
     }
 
     fn uci(&mut self) {
         writeln!(self.out.lock().unwrap(), "id name silverfish").unwrap();
+        for option in self.options.iter() {
+            writeln!(self.out.lock().unwrap(), "{}", option).unwrap();
+        }
         writeln!(self.out.lock().unwrap(), "uciok").unwrap();
     }
 
@@ -204,13 +341,13 @@ impl UciHandler {
 
     fn perft(&mut self, command: &str) {
         let start = std::time::Instant::now();
-        let depth: u8 = command.split_whitespace().skip(1).next().unwrap().parse().unwrap();
+        let depth: u8 = command.split_whitespace().nth(1).unwrap().parse().unwrap();
         
         // We wish to find all legal moves, sorted by (from, to) (with promotion piece in desc order, if applicable)
-        let mut moves = generate_moves(&self.state);
+        let moves = generate_moves(&self.state);
         let mut legal_moves = Vec::new();
-        while let Some(r#move) = moves.next() {
-            let copy = self.state.clone();
+        for r#move in moves {
+            let copy = self.state;
             if self.state.make_move(r#move).is_err() {
                 continue;
             }
@@ -224,7 +361,7 @@ impl UciHandler {
         
         let mut total = 0;
         for r#move in legal_moves {
-            let copy = self.state.clone();
+            let copy = self.state;
             self.state.make_move(r#move).unwrap();
             let n = perft(&mut self.state, depth-1);
             self.state = copy;
@@ -236,10 +373,51 @@ impl UciHandler {
     }
 
     fn eval(&mut self) {
-        writeln!(self.out.lock().unwrap(), "{}", eval(&self.state)).unwrap();
+        let score = match self.network() {
+            Some(network) => match self.state.to_move {
+                Colour::White => network.evaluate_state(&self.state),
+                Colour::Black => -network.evaluate_state(&self.state)
+            },
+            None => eval(&self.state)
+        };
+        writeln!(self.out.lock().unwrap(), "{}", score).unwrap();
     }
 
     fn print(&mut self) {
         writeln!(self.out.lock().unwrap(), "{}", self.state).unwrap();
     }
+}
+
+// Finds the legal move in the given position matching a long algebraic move string, e.g. "e7e8q"
+fn parse_move(state: &State, move_string: &str) -> Option<BitMove> {
+    if !move_string_is_valid(move_string) {
+        return None;
+    }
+
+    let from = algebraic_to_sq(&move_string[0..2]);
+    let to = algebraic_to_sq(&move_string[2..4]);
+    let promotion_piece = match move_string.get(4..5) {
+        Some("n") => Some(Piece::Knight),
+        Some("b") => Some(Piece::Bishop),
+        Some("r") => Some(Piece::Rook),
+        Some("q") => Some(Piece::Queen),
+        _ => None
+    };
+
+    generate_moves(state).find(|r#move| {
+        let mut copy = *state;
+        from == move_from(*r#move) && to == move_to(*r#move) && promotion_piece == move_promotion_piece(*r#move) && copy.make_move(*r#move).is_ok()
+    })
+}
+
+// Clock values can be negative if a GUI lets us overstep the time control, so those are treated as zero
+fn parse_millis(millis: &str) -> Option<Duration> {
+    millis.parse::<i64>().ok().map(|ms| Duration::from_millis(max(ms, 0) as u64))
+}
+
+fn format_score(score: isize) -> String {
+    match mate_in(score) {
+        Some(n) => format!("mate {}", n),
+        None => format!("cp {}", score)
+    }
 }
\ No newline at end of file